@@ -0,0 +1,3247 @@
+//! The deck model behind the `flashcard` CLI: cards, their review statistics and
+//! scheduling, decks and the files they are saved in.
+
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// The deck used when no `--deck` is given, and the one single-deck files are loaded into.
+pub const DEFAULT_DECK: &str = "default";
+
+/// The name of the deck file in the flashcards directory.
+pub const DECK_FILE_NAME: &str = "flashcards.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Flashcard {
+    pub id: u32,
+    pub question: String,
+    pub answer: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Path to an image or other file that goes with the card
+    #[serde(default)]
+    pub media: Option<String>,
+    #[serde(default)]
+    pub kind: CardKind,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub archived: bool,
+    pub metadata: CardMetadata,
+}
+
+impl Flashcard {
+    /// Whether the card has `tag` and `difficulty`, where either criterion can be left out.
+    pub fn matches(&self, tag: Option<&str>, difficulty: Option<&Difficulty>) -> bool {
+        tag.is_none_or(|tag| self.has_tag(&normalize_tag(tag)))
+            && difficulty.is_none_or(|difficulty| self.metadata.difficulty == *difficulty)
+    }
+
+    /// The hidden spans of a cloze card, in order. Basic cards have none.
+    pub fn cloze_blanks(&self) -> Vec<String> {
+        match self.kind {
+            CardKind::Basic => Vec::new(),
+            CardKind::Cloze => parse_cloze(&self.question).unwrap_or_default(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Whether the answer just repeats the question, usually a sign of a bad import.
+    pub fn answer_repeats_question(&self) -> bool {
+        self.kind == CardKind::Basic
+            && normalize_question(&self.answer) == normalize_question(&self.question)
+    }
+
+    /// Whether the answer has more than `limit` characters.
+    pub fn answer_longer_than(&self, limit: usize) -> bool {
+        self.answer.chars().count() > limit
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CardMetadata {
+    pub difficulty: Difficulty,
+    pub times_reviewed: u32,
+    pub correct_count: u32,
+    #[serde(default)]
+    pub last_reviewed: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default = "default_ease_factor")]
+    pub ease_factor: f64,
+    #[serde(default)]
+    pub interval_days: u32,
+    #[serde(default)]
+    pub repetitions: u32,
+    #[serde(default)]
+    pub due_date: Option<chrono::NaiveDate>,
+    #[serde(default = "default_box_number")]
+    pub box_number: u8,
+    #[serde(default)]
+    pub avg_response_secs: Option<f64>,
+    #[serde(default)]
+    pub timed_reviews: u32,
+    #[serde(default)]
+    pub history: Vec<ReviewEvent>,
+    #[serde(default)]
+    pub consecutive_correct: u32,
+    #[serde(default)]
+    pub mastered: bool,
+    /// Review the card every this many days, whatever the scheduler would do
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed_interval_days: Option<u32>,
+    /// How much more often `quiz --smart` picks the card, from 1 to `MAX_WEIGHT`
+    #[serde(default = "default_weight")]
+    pub weight: u8,
+    /// Results and schedule of the answer → question direction. The fields above count
+    /// reviews in both directions but schedule only the question → answer one.
+    #[serde(default, skip_serializing_if = "DirectionStats::is_empty")]
+    pub reverse: DirectionStats,
+}
+
+/// Results and SM-2 schedule of one direction of a card.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DirectionStats {
+    pub times_reviewed: u32,
+    pub correct_count: u32,
+    pub last_reviewed: Option<chrono::DateTime<chrono::Utc>>,
+    pub ease_factor: f64,
+    pub interval_days: u32,
+    pub repetitions: u32,
+    pub due_date: Option<chrono::NaiveDate>,
+}
+
+impl Default for DirectionStats {
+    fn default() -> Self {
+        DirectionStats {
+            times_reviewed: 0,
+            correct_count: 0,
+            last_reviewed: None,
+            ease_factor: default_ease_factor(),
+            interval_days: 0,
+            repetitions: 0,
+            due_date: None,
+        }
+    }
+}
+
+impl DirectionStats {
+    /// Whether this direction was never reviewed or scheduled.
+    pub fn is_empty(&self) -> bool {
+        *self == DirectionStats::default()
+    }
+}
+
+/// Correct answers in a row after which a card is mastered, unless a quiz sets another.
+pub const DEFAULT_MASTERY_THRESHOLD: u32 = 5;
+
+/// Which cards a quiz draws from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CardSelection {
+    pub starred_only: bool,
+    pub include_archived: bool,
+    pub include_mastered: bool,
+}
+
+impl CardSelection {
+    pub fn includes(&self, card: &Flashcard) -> bool {
+        (!self.starred_only || card.starred)
+            && (self.include_archived || !card.archived)
+            && (self.include_mastered || !card.metadata.mastered)
+    }
+}
+
+/// How many of the latest reviews `CardMetadata::trend` looks at.
+pub const TREND_WINDOW: usize = 5;
+/// How far apart the recent and overall success rates must be to count as a change.
+const TREND_THRESHOLD: f64 = 0.1;
+
+/// Whether a card has been doing better or worse lately than it does overall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Improving,
+    Declining,
+    Steady,
+}
+
+impl Trend {
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Trend::Improving => "↑",
+            Trend::Declining => "↓",
+            Trend::Steady => "→",
+        }
+    }
+}
+
+/// A single review of a card, kept in the order the reviews happened.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewEvent {
+    pub date: chrono::DateTime<chrono::Utc>,
+    pub correct: bool,
+    pub quality: u8,
+    /// The card's difficulty when it was reviewed; unknown for reviews recorded before it was
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+}
+
+impl CardMetadata {
+    /// Clear the review statistics and schedule, keeping the settings chosen for the card:
+    /// its weight and fixed review interval.
+    pub fn reset_stats(&mut self) {
+        *self = CardMetadata {
+            fixed_interval_days: self.fixed_interval_days,
+            weight: self.weight,
+            ..CardMetadata::default()
+        };
+    }
+
+    /// Correct and total reviews for each difficulty the card had when it was reviewed,
+    /// from easiest to hardest. Only difficulties with recorded reviews are included.
+    pub fn results_by_difficulty(&self) -> Vec<(Difficulty, u32, u32)> {
+        let mut results: Vec<(Difficulty, u32, u32)> = Vec::new();
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let reviews = self
+                .history
+                .iter()
+                .filter(|event| event.difficulty.as_ref() == Some(&difficulty));
+            let (correct, total) = reviews.fold((0, 0), |(correct, total), event| {
+                (correct + u32::from(event.correct), total + 1)
+            });
+            if total > 0 {
+                results.push((difficulty, correct, total));
+            }
+        }
+        results
+    }
+
+    /// Fold another copy's reviews into these statistics, keeping this copy's schedule.
+    pub fn absorb(&mut self, other: &CardMetadata) {
+        let timed_reviews = self.timed_reviews + other.timed_reviews;
+        if timed_reviews > 0 {
+            let total_secs = |metadata: &CardMetadata| {
+                metadata.avg_response_secs.unwrap_or(0.0) * f64::from(metadata.timed_reviews)
+            };
+            self.avg_response_secs =
+                Some((total_secs(self) + total_secs(other)) / f64::from(timed_reviews));
+        }
+        self.timed_reviews = timed_reviews;
+        self.times_reviewed += other.times_reviewed;
+        self.correct_count += other.correct_count;
+        self.last_reviewed = self.last_reviewed.max(other.last_reviewed);
+        self.reverse.times_reviewed += other.reverse.times_reviewed;
+        self.reverse.correct_count += other.reverse.correct_count;
+        self.reverse.last_reviewed = self.reverse.last_reviewed.max(other.reverse.last_reviewed);
+        self.history.extend(other.history.iter().cloned());
+        self.history.sort_by_key(|event| event.date);
+    }
+
+    /// Correct answers and reviews in one direction: question → answer, or answer → question
+    /// when `reversed`.
+    pub fn direction_results(&self, reversed: bool) -> (u32, u32) {
+        if reversed {
+            (self.reverse.correct_count, self.reverse.times_reviewed)
+        } else {
+            (
+                self.correct_count
+                    .saturating_sub(self.reverse.correct_count),
+                self.times_reviewed
+                    .saturating_sub(self.reverse.times_reviewed),
+            )
+        }
+    }
+
+    /// Percentage of reviews answered correctly, or 0 when never reviewed.
+    pub fn success_rate(&self) -> f64 {
+        if self.times_reviewed > 0 {
+            (self.correct_count as f64 / self.times_reviewed as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the last review fell on a local day within `since..=until`. A card that was
+    /// never reviewed only matches when neither bound is given.
+    pub fn reviewed_between(
+        &self,
+        since: Option<chrono::NaiveDate>,
+        until: Option<chrono::NaiveDate>,
+    ) -> bool {
+        match self.last_reviewed {
+            Some(date) => {
+                let day = date.with_timezone(&chrono::Local).date_naive();
+                since.is_none_or(|since| day >= since) && until.is_none_or(|until| day <= until)
+            }
+            None => since.is_none() && until.is_none(),
+        }
+    }
+
+    /// The day the card is next due: its fixed interval after the last review when it has
+    /// one, otherwise the date set by SM-2. `None` means it is due whenever it is asked.
+    pub fn next_due_date(&self) -> Option<chrono::NaiveDate> {
+        match self.fixed_interval_days {
+            Some(days) => self
+                .last_reviewed
+                .map(|date| days_after(date.date_naive(), days)),
+            None => self.due_date,
+        }
+    }
+
+    /// A card is due when it has never been scheduled or its due date has passed.
+    pub fn is_due(&self, today: chrono::NaiveDate) -> bool {
+        self.next_due_date().is_none_or(|due| due <= today)
+    }
+
+    /// Like `is_due`, for the answer → question direction when `reversed`. A fixed interval
+    /// applies to both directions alike.
+    pub fn is_due_in(&self, today: chrono::NaiveDate, reversed: bool) -> bool {
+        if !reversed || self.fixed_interval_days.is_some() {
+            return self.is_due(today);
+        }
+        self.reverse.due_date.is_none_or(|due| due <= today)
+    }
+
+    /// Whether the card's Leitner box interval has passed since its last review.
+    /// A fixed interval takes the place of the box's.
+    pub fn is_leitner_due(&self, today: chrono::NaiveDate) -> bool {
+        if self.fixed_interval_days.is_some() {
+            return self.is_due(today);
+        }
+        let interval =
+            LEITNER_INTERVAL_DAYS[usize::from(self.box_number.clamp(1, LEITNER_BOXES)) - 1];
+        self.last_reviewed
+            .is_none_or(|date| (today - date.date_naive()).num_days() >= interval)
+    }
+
+    /// Compare the success rate of the last `TREND_WINDOW` reviews with the overall one.
+    /// Needs review history, and more reviews than the window so the two can differ.
+    pub fn trend(&self) -> Option<Trend> {
+        if self.history.len() <= TREND_WINDOW || self.times_reviewed == 0 {
+            return None;
+        }
+        let recent = &self.history[self.history.len() - TREND_WINDOW..];
+        let recent_rate =
+            recent.iter().filter(|event| event.correct).count() as f64 / TREND_WINDOW as f64;
+        let overall_rate = self.correct_count as f64 / self.times_reviewed as f64;
+        Some(if recent_rate > overall_rate + TREND_THRESHOLD {
+            Trend::Improving
+        } else if recent_rate < overall_rate - TREND_THRESHOLD {
+            Trend::Declining
+        } else {
+            Trend::Steady
+        })
+    }
+
+    /// How urgently the card should be quizzed: the failure rate plus a bonus that grows with
+    /// the days since the last review, both between 0 and 1, multiplied by the card's weight.
+    /// Never-reviewed cards score highest for their weight.
+    pub fn priority(&self, today: chrono::NaiveDate) -> f64 {
+        let weight = f64::from(self.weight.clamp(1, MAX_WEIGHT));
+        let Some(last_reviewed) = self.last_reviewed.filter(|_| self.times_reviewed > 0) else {
+            return 3.0 * weight;
+        };
+        let failure_rate = 1.0 - self.correct_count as f64 / self.times_reviewed as f64;
+        let days = (today - last_reviewed.date_naive()).num_days().max(0) as f64;
+        (failure_rate + days / (days + 7.0)) * weight
+    }
+
+    /// The date a card became due. Unscheduled cards fall back to their last review,
+    /// and cards that were never reviewed are treated as the most overdue.
+    pub fn effective_due_date(&self) -> chrono::NaiveDate {
+        self.next_due_date()
+            .or(self.last_reviewed.map(|date| date.date_naive()))
+            .unwrap_or(chrono::NaiveDate::MIN)
+    }
+}
+
+/// Trim and lowercase tags, dropping empty and repeated ones while keeping their order.
+/// A tag containing a comma, the tag separator, is rejected.
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = normalize_tag(&tag);
+        if tag.contains(',') {
+            return Err(format!("Tags can't contain commas: '{}'", tag));
+        }
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+/// The form a tag is stored and looked up in.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// The form of a question used to detect duplicates.
+pub fn normalize_question(question: &str) -> String {
+    question.trim().to_lowercase()
+}
+
+/// Trim a question or answer, rejecting it when nothing is left.
+pub fn clean_card_text(field: &str, text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        Err(format!("The {} can't be empty.", field))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+fn default_ease_factor() -> f64 {
+    2.5
+}
+
+fn default_box_number() -> u8 {
+    1
+}
+
+/// The longest interval SM-2 schedules a card for, about a hundred years. Without a cap the
+/// interval grows exponentially and soon overflows the calendar.
+pub const MAX_INTERVAL_DAYS: u32 = 36500;
+
+/// The date `days` after `date`, or the last representable date if that is out of range.
+fn days_after(date: chrono::NaiveDate, days: u32) -> chrono::NaiveDate {
+    date.checked_add_days(chrono::Days::new(days.into()))
+        .unwrap_or(chrono::NaiveDate::MAX)
+}
+
+/// Apply one SM-2 review of `quality` (0-5) to a schedule's ease factor, interval and
+/// repetition count.
+fn sm2_review(ease_factor: &mut f64, interval_days: &mut u32, repetitions: &mut u32, quality: u8) {
+    let quality = quality.min(5);
+    if quality >= 3 {
+        *interval_days = match *repetitions {
+            0 => 1,
+            1 => 6,
+            _ => ((*interval_days as f64 * *ease_factor).round() as u32).min(MAX_INTERVAL_DAYS),
+        };
+        *repetitions += 1;
+    } else {
+        *repetitions = 0;
+        *interval_days = 1;
+    }
+
+    let penalty = (5 - quality) as f64;
+    *ease_factor = (*ease_factor + (0.1 - penalty * (0.08 + penalty * 0.02))).max(1.3);
+}
+
+/// The highest weight a card can be given.
+pub const MAX_WEIGHT: u8 = 10;
+
+fn default_weight() -> u8 {
+    1
+}
+
+/// Number of Leitner boxes; cards in box N are reviewed every `LEITNER_INTERVAL_DAYS[N - 1]` days.
+pub const LEITNER_BOXES: u8 = 5;
+pub const LEITNER_INTERVAL_DAYS: [i64; LEITNER_BOXES as usize] = [1, 2, 4, 8, 16];
+
+/// The current calendar day in the user's time zone.
+pub fn today() -> chrono::NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+pub fn format_review_date(date: &chrono::DateTime<chrono::Utc>) -> String {
+    date.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// A review date both as a date and relative to now, like `2024-01-05 09:30 (3 days ago)`.
+pub fn format_last_reviewed(date: &chrono::DateTime<chrono::Utc>) -> String {
+    format!(
+        "{} ({})",
+        format_review_date(date),
+        humanize_since(*date, chrono::Utc::now())
+    )
+}
+
+/// How long before `now` a date was, in the largest whole unit, like `2 weeks ago`.
+pub fn humanize_since(
+    date: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let elapsed = now - date;
+    if elapsed.num_minutes() < 1 {
+        return "just now".to_string();
+    }
+    let (count, unit) = if elapsed.num_hours() < 1 {
+        (elapsed.num_minutes(), "minute")
+    } else if elapsed.num_days() < 1 {
+        (elapsed.num_hours(), "hour")
+    } else if elapsed.num_weeks() < 1 {
+        (elapsed.num_days(), "day")
+    } else if elapsed.num_days() < 30 {
+        (elapsed.num_weeks(), "week")
+    } else if elapsed.num_days() < 365 {
+        (elapsed.num_days() / 30, "month")
+    } else {
+        (elapsed.num_days() / 365, "year")
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// One step easier, staying at Easy.
+    pub fn easier(&self) -> Difficulty {
+        match self {
+            Difficulty::Hard => Difficulty::Medium,
+            Difficulty::Medium | Difficulty::Easy => Difficulty::Easy,
+        }
+    }
+
+    /// One step harder, staying at Hard.
+    pub fn harder(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Medium,
+            Difficulty::Medium | Difficulty::Hard => Difficulty::Hard,
+        }
+    }
+}
+
+/// Whether a card is a plain question/answer pair or a fill-in-the-blank sentence.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CardKind {
+    #[default]
+    Basic,
+    /// The question holds text with `{{...}}` blanks and the answer lists them.
+    Cloze,
+}
+
+/// Extract the `{{...}}` blanks of a cloze text, rejecting unbalanced or empty markers.
+pub fn parse_cloze(text: &str) -> Result<Vec<String>, String> {
+    let mut blanks = Vec::new();
+    let mut rest = text;
+    loop {
+        match (rest.find("{{"), rest.find("}}")) {
+            (None, None) => break,
+            (Some(_), None) => return Err("found `{{` without a closing `}}`".to_string()),
+            (None, Some(_)) => return Err("found `}}` without an opening `{{`".to_string()),
+            (Some(open), Some(close)) if close < open => {
+                return Err("found `}}` without an opening `{{`".to_string());
+            }
+            (Some(open), Some(close)) => {
+                let blank = &rest[open + 2..close];
+                if blank.contains("{{") {
+                    return Err("blanks can't be nested".to_string());
+                }
+                if blank.trim().is_empty() {
+                    return Err("found an empty `{{}}` blank".to_string());
+                }
+                blanks.push(blank.trim().to_string());
+                rest = &rest[close + 2..];
+            }
+        }
+    }
+    if blanks.is_empty() {
+        return Err("mark at least one blank like {{this}}".to_string());
+    }
+    Ok(blanks)
+}
+
+/// Render a cloze text with its blanks hidden as `[...]`, or revealed in brackets.
+pub fn render_cloze(text: &str, reveal: bool) -> String {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find("{{")
+        && let Some(close) = rest[open..].find("}}").map(|close| open + close)
+    {
+        rendered.push_str(&rest[..open]);
+        if reveal {
+            rendered.push_str(&format!("[{}]", rest[open + 2..close].trim()));
+        } else {
+            rendered.push_str("[...]");
+        }
+        rest = &rest[close + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The prompt and answer shown for a card, with cloze blanks hidden in the prompt.
+pub fn card_sides(card: &Flashcard, reversed: bool) -> (String, String) {
+    match card.kind {
+        CardKind::Cloze => (
+            render_cloze(&card.question, false),
+            render_cloze(&card.question, true),
+        ),
+        CardKind::Basic if reversed => (card.answer.clone(), card.question.clone()),
+        CardKind::Basic => (card.question.clone(), card.answer.clone()),
+    }
+}
+
+/// How list-style commands order their cards.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortKey {
+    Id,
+    Difficulty,
+    Success,
+    Reviews,
+}
+
+/// Sort cards for display. Cards that were never reviewed always sort last by success rate.
+pub fn sort_cards(cards: &mut [&Flashcard], key: SortKey, reverse: bool) {
+    cards.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Id => a.id.cmp(&b.id),
+            SortKey::Difficulty => a.metadata.difficulty.cmp(&b.metadata.difficulty),
+            SortKey::Reviews => a.metadata.times_reviewed.cmp(&b.metadata.times_reviewed),
+            SortKey::Success => {
+                let unreviewed = (
+                    a.metadata.times_reviewed == 0,
+                    b.metadata.times_reviewed == 0,
+                );
+                match unreviewed {
+                    (true, true) => std::cmp::Ordering::Equal,
+                    (true, false) => return std::cmp::Ordering::Greater,
+                    (false, true) => return std::cmp::Ordering::Less,
+                    (false, false) => a
+                        .metadata
+                        .success_rate()
+                        .total_cmp(&b.metadata.success_rate()),
+                }
+            }
+        };
+        let ordering = if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then(a.id.cmp(&b.id))
+    });
+}
+
+/// Which card fields a search looks at.
+#[derive(Clone, Copy)]
+pub enum SearchScope {
+    All,
+    Questions,
+    Answers,
+}
+
+/// Whether `text` contains `query`, ignoring case unless `case_sensitive` is set.
+fn text_contains(text: &str, query: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        text.contains(query)
+    } else {
+        text.to_lowercase().contains(&query.to_lowercase())
+    }
+}
+
+/// Cards sharing a normalized question, and the one that survives a merge.
+#[derive(Debug, PartialEq)]
+pub struct DuplicateGroup {
+    pub keep: u32,
+    pub duplicates: Vec<u32>,
+}
+
+/// Aggregate statistics over every card in a deck.
+#[derive(Serialize, Debug, Default)]
+pub struct DeckStats {
+    pub total_cards: usize,
+    pub total_reviews: u32,
+    pub total_correct: u32,
+    pub success_rate: f64,
+    pub easy_cards: usize,
+    pub medium_cards: usize,
+    pub hard_cards: usize,
+    pub never_reviewed: usize,
+    pub most_reviewed: Option<u32>,
+    pub least_reviewed: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lengths: Option<LengthStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_tag: Option<HashMap<String, TagStats>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_quiz_percentage: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_quiz_date: Option<chrono::NaiveDate>,
+}
+
+/// Review totals over the cards carrying one tag.
+#[derive(Serialize, Debug, Default)]
+pub struct TagStats {
+    pub cards: usize,
+    pub reviews: u32,
+    pub correct: u32,
+    pub success_rate: f64,
+}
+
+/// Character and word counts across one side of the cards.
+#[derive(Serialize, Debug, Default)]
+pub struct TextLengths {
+    pub avg_chars: f64,
+    pub min_chars: usize,
+    pub max_chars: usize,
+    pub avg_words: f64,
+    pub min_words: usize,
+    pub max_words: usize,
+    pub total_words: usize,
+}
+
+impl TextLengths {
+    fn measure<'a>(texts: impl Iterator<Item = &'a str>) -> Self {
+        let counts: Vec<(usize, usize)> = texts
+            .map(|text| (text.chars().count(), text.split_whitespace().count()))
+            .collect();
+        if counts.is_empty() {
+            return TextLengths::default();
+        }
+        let total_chars: usize = counts.iter().map(|(chars, _)| chars).sum();
+        let total_words: usize = counts.iter().map(|(_, words)| words).sum();
+        TextLengths {
+            avg_chars: total_chars as f64 / counts.len() as f64,
+            min_chars: counts.iter().map(|(chars, _)| *chars).min().unwrap_or(0),
+            max_chars: counts.iter().map(|(chars, _)| *chars).max().unwrap_or(0),
+            avg_words: total_words as f64 / counts.len() as f64,
+            min_words: counts.iter().map(|(_, words)| *words).min().unwrap_or(0),
+            max_words: counts.iter().map(|(_, words)| *words).max().unwrap_or(0),
+            total_words,
+        }
+    }
+}
+
+/// How long the questions and answers in a deck are.
+#[derive(Serialize, Debug, Default)]
+pub struct LengthStats {
+    pub questions: TextLengths,
+    pub answers: TextLengths,
+    pub total_words: usize,
+}
+
+/// How many cards an import created, and how many of its entries it couldn't use.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub skipped: usize,
+}
+
+/// How many cards a merge added, folded into an existing card, or left out as duplicates.
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub merged: usize,
+    pub skipped: usize,
+}
+
+/// A card together with the statistics computed from it, for JSON output.
+#[derive(Serialize)]
+pub struct CardReport<'a> {
+    #[serde(flatten)]
+    card: &'a Flashcard,
+    success_rate: f64,
+}
+
+impl<'a> CardReport<'a> {
+    pub fn new(card: &'a Flashcard) -> Self {
+        CardReport {
+            card,
+            success_rate: card.metadata.success_rate(),
+        }
+    }
+}
+
+/// A column of a `csv` export, named after its header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum CsvField {
+    Id,
+    Question,
+    Answer,
+    Difficulty,
+    #[cfg_attr(feature = "cli", value(name = "times_reviewed"))]
+    TimesReviewed,
+    #[cfg_attr(feature = "cli", value(name = "correct_count"))]
+    CorrectCount,
+}
+
+impl CsvField {
+    /// Every column, in the order a `csv` export writes them by default.
+    pub const ALL: [CsvField; 6] = [
+        CsvField::Id,
+        CsvField::Question,
+        CsvField::Answer,
+        CsvField::Difficulty,
+        CsvField::TimesReviewed,
+        CsvField::CorrectCount,
+    ];
+
+    fn header(self) -> &'static str {
+        match self {
+            CsvField::Id => "id",
+            CsvField::Question => "question",
+            CsvField::Answer => "answer",
+            CsvField::Difficulty => "difficulty",
+            CsvField::TimesReviewed => "times_reviewed",
+            CsvField::CorrectCount => "correct_count",
+        }
+    }
+
+    fn value(self, card: &Flashcard) -> String {
+        match self {
+            CsvField::Id => card.id.to_string(),
+            CsvField::Question => card.question.clone(),
+            CsvField::Answer => card.answer.clone(),
+            CsvField::Difficulty => format!("{:?}", card.metadata.difficulty),
+            CsvField::TimesReviewed => card.metadata.times_reviewed.to_string(),
+            CsvField::CorrectCount => card.metadata.correct_count.to_string(),
+        }
+    }
+}
+
+/// A deck wrapped with its schema version, as written by a `json` export.
+#[derive(Serialize)]
+struct DeckEnvelope<'a> {
+    schema: u32,
+    deck: &'a FlashcardDeck,
+}
+
+/// One review in a `stats-csv` export, with the success rate of every review up to it.
+#[derive(Serialize)]
+struct ReviewCsvRow {
+    date: String,
+    card_id: u32,
+    correct: bool,
+    success_rate: String,
+}
+
+/// Aggregate `stats-csv` row for decks saved before review history was kept.
+#[derive(Serialize)]
+struct CardStatsCsvRow {
+    last_reviewed: String,
+    card_id: u32,
+    times_reviewed: u32,
+    correct_count: u32,
+    success_rate: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlashcardDeck {
+    pub cards: HashMap<u32, Flashcard>,
+    pub next_id: u32,
+    #[serde(default)]
+    pub current_streak: u32,
+    #[serde(default)]
+    pub longest_streak: u32,
+    #[serde(default)]
+    pub last_study_date: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub daily_goal: u32,
+    #[serde(default)]
+    pub best_quiz_percentage: Option<f64>,
+    #[serde(default)]
+    pub best_quiz_date: Option<chrono::NaiveDate>,
+}
+
+impl FlashcardDeck {
+    pub fn new() -> Self {
+        FlashcardDeck {
+            cards: HashMap::new(),
+            next_id: 1,
+            current_streak: 0,
+            longest_streak: 0,
+            last_study_date: None,
+            daily_goal: 0,
+            best_quiz_percentage: None,
+            best_quiz_date: None,
+        }
+    }
+
+    /// A copy of the deck with its cards given IDs 1, 2, 3... in their current order, and
+    /// `next_id` following the last of them. Everything else about the cards is kept.
+    pub fn renumbered(&self) -> FlashcardDeck {
+        let mut cards: Vec<&Flashcard> = self.cards.values().collect();
+        cards.sort_by_key(|card| card.id);
+        let cards: HashMap<u32, Flashcard> = cards
+            .into_iter()
+            .zip(1..)
+            .map(|(card, id)| (id, Flashcard { id, ..card.clone() }))
+            .collect();
+        FlashcardDeck {
+            next_id: cards.len() as u32 + 1,
+            cards,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the card IDs already run 1, 2, 3... without gaps.
+    pub fn ids_are_sequential(&self) -> bool {
+        (1..=self.cards.len() as u32).all(|id| self.cards.contains_key(&id))
+    }
+
+    /// Add the built-in sample cards, tagged `sample`. Returns how many were added.
+    pub fn add_sample_cards(&mut self) -> usize {
+        for (question, answer) in SAMPLE_CARDS {
+            let id = self.add_card(question.to_string(), answer.to_string());
+            self.update_card_tags(id, vec![SAMPLE_TAG.to_string()], Vec::new());
+        }
+        SAMPLE_CARDS.len()
+    }
+
+    pub fn add_card(&mut self, question: String, answer: String) -> u32 {
+        self.insert_card(question, answer, CardKind::Basic)
+    }
+
+    /// Add a fill-in-the-blank card. The answer is derived from the text's blanks.
+    pub fn add_cloze_card(&mut self, text: String) -> Result<u32, String> {
+        let answer = parse_cloze(&text)?.join(", ");
+        Ok(self.insert_card(text, answer, CardKind::Cloze))
+    }
+
+    /// The ID for the next new card. A hand-edited file can leave `next_id` at or below an
+    /// existing ID, so it's never allowed to fall behind the highest one in use.
+    fn fresh_id(&self) -> u32 {
+        let after_highest = self.cards.keys().max().map_or(1, |highest| highest + 1);
+        self.next_id.max(after_highest)
+    }
+
+    fn insert_card(&mut self, question: String, answer: String, kind: CardKind) -> u32 {
+        self.insert_with_fresh_id(Flashcard {
+            id: 0,
+            question,
+            answer,
+            tags: Vec::new(),
+            notes: None,
+            media: None,
+            kind,
+            starred: false,
+            archived: false,
+            metadata: CardMetadata::default(),
+        })
+    }
+
+    /// Insert a card, such as one taken from another deck, under a fresh ID.
+    pub fn insert_with_fresh_id(&mut self, mut card: Flashcard) -> u32 {
+        card.id = self.fresh_id();
+        let id = card.id;
+        self.cards.insert(id, card);
+        self.next_id = id + 1;
+        id
+    }
+
+    /// Find a card whose question matches, ignoring case and surrounding whitespace.
+    pub fn find_duplicate(&self, question: &str) -> Option<u32> {
+        let question = normalize_question(question);
+        self.cards
+            .values()
+            .filter(|card| normalize_question(&card.question) == question)
+            .map(|card| card.id)
+            .min()
+    }
+
+    /// Add copies of another deck's cards under fresh IDs. A card whose question is already
+    /// in this deck is skipped, or with `merge_stats` has its reviews folded into that card.
+    pub fn merge_deck(&mut self, other: &FlashcardDeck, merge_stats: bool) -> MergeSummary {
+        let mut cards: Vec<&Flashcard> = other.cards.values().collect();
+        cards.sort_by_key(|card| card.id);
+
+        let mut summary = MergeSummary::default();
+        for card in cards {
+            match self.find_duplicate(&card.question) {
+                Some(existing) if merge_stats => {
+                    let existing = self.cards.get_mut(&existing).unwrap();
+                    existing.metadata.absorb(&card.metadata);
+                    for tag in &card.tags {
+                        if !existing.has_tag(tag) {
+                            existing.tags.push(tag.clone());
+                        }
+                    }
+                    summary.merged += 1;
+                }
+                Some(_) => summary.skipped += 1,
+                None => {
+                    self.insert_with_fresh_id(card.clone());
+                    summary.added += 1;
+                }
+            }
+        }
+        summary
+    }
+
+    /// Group cards by normalized question, keeping the most reviewed card of each group.
+    pub fn duplicate_groups(&self) -> Vec<DuplicateGroup> {
+        let mut by_question: HashMap<String, Vec<&Flashcard>> = HashMap::new();
+        for card in self.cards.values() {
+            by_question
+                .entry(normalize_question(&card.question))
+                .or_default()
+                .push(card);
+        }
+
+        let mut groups: Vec<DuplicateGroup> = by_question
+            .into_values()
+            .filter(|cards| cards.len() > 1)
+            .map(|mut cards| {
+                cards
+                    .sort_by_key(|card| (std::cmp::Reverse(card.metadata.times_reviewed), card.id));
+                DuplicateGroup {
+                    keep: cards[0].id,
+                    duplicates: cards[1..].iter().map(|card| card.id).collect(),
+                }
+            })
+            .collect();
+        groups.sort_by_key(|group| group.keep);
+        groups
+    }
+
+    /// Fold the reviews of each group's duplicates into the kept card and delete them.
+    pub fn merge_duplicates(&mut self, groups: &[DuplicateGroup]) -> usize {
+        let mut removed = 0;
+        for group in groups {
+            for id in &group.duplicates {
+                let Some(duplicate) = self.cards.remove(id) else {
+                    continue;
+                };
+                if let Some(card) = self.cards.get_mut(&group.keep) {
+                    card.metadata.absorb(&duplicate.metadata);
+                }
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Record a review and set the card's new difficulty. `times_reviewed` and `correct_count`
+    /// are kept as a cache of the history. `reversed` reviews, answer → question, also count
+    /// towards the reverse direction's results.
+    pub fn update_card_difficulty(
+        &mut self,
+        card_id: u32,
+        difficulty: Difficulty,
+        correct: bool,
+        quality: u8,
+        reversed: bool,
+    ) {
+        if let Some(card) = self.cards.get_mut(&card_id) {
+            let now = chrono::Utc::now();
+            let previous = std::mem::replace(&mut card.metadata.difficulty, difficulty);
+            card.metadata.times_reviewed += 1;
+            if correct {
+                card.metadata.correct_count += 1;
+                card.metadata.consecutive_correct += 1;
+            } else {
+                card.metadata.consecutive_correct = 0;
+            }
+            card.metadata.last_reviewed = Some(now);
+            if reversed {
+                let reverse = &mut card.metadata.reverse;
+                reverse.times_reviewed += 1;
+                reverse.correct_count += u32::from(correct);
+                reverse.last_reviewed = Some(now);
+            }
+            card.metadata.history.push(ReviewEvent {
+                date: now,
+                correct,
+                quality,
+                difficulty: Some(previous),
+            });
+        }
+    }
+
+    pub fn update_card_text(
+        &mut self,
+        card_id: u32,
+        question: Option<String>,
+        answer: Option<String>,
+    ) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                if let Some(question) = question {
+                    card.question = question;
+                }
+                if let Some(answer) = answer {
+                    card.answer = answer;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the media file of a card; an empty path removes it.
+    pub fn update_card_media(&mut self, card_id: u32, media: String) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.media = Some(media).filter(|media| !media.trim().is_empty());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the notes of a card; empty notes remove them.
+    pub fn update_card_notes(&mut self, card_id: u32, notes: String) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.notes = Some(notes).filter(|notes| !notes.trim().is_empty());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Update the SM-2 scheduling fields of a card from a review quality (0-5), in the
+    /// answer → question direction when `reversed`.
+    pub fn schedule_card(&mut self, card_id: u32, quality: u8, reversed: bool) {
+        let today = today();
+        if let Some(card) = self.cards.get_mut(&card_id) {
+            let metadata = &mut card.metadata;
+            if reversed {
+                let reverse = &mut metadata.reverse;
+                sm2_review(
+                    &mut reverse.ease_factor,
+                    &mut reverse.interval_days,
+                    &mut reverse.repetitions,
+                    quality,
+                );
+                reverse.due_date = Some(days_after(today, reverse.interval_days));
+            } else {
+                sm2_review(
+                    &mut metadata.ease_factor,
+                    &mut metadata.interval_days,
+                    &mut metadata.repetitions,
+                    quality,
+                );
+                metadata.due_date = Some(days_after(today, metadata.interval_days));
+            }
+        }
+    }
+
+    /// Add and remove tags on a card, ignoring tags it already has.
+    pub fn update_card_tags(
+        &mut self,
+        card_id: u32,
+        add: Vec<String>,
+        remove: Vec<String>,
+    ) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                for tag in add {
+                    if !card.has_tag(&tag) {
+                        card.tags.push(tag);
+                    }
+                }
+                card.tags.retain(|t| !remove.contains(t));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace `from` with `to` on every card, without giving a card the same tag twice.
+    /// Returns how many cards had the tag.
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> usize {
+        let mut renamed = 0;
+        for card in self.cards.values_mut().filter(|card| card.has_tag(from)) {
+            renamed += 1;
+            if from == to {
+                continue;
+            }
+            if card.has_tag(to) {
+                card.tags.retain(|tag| tag != from);
+            } else {
+                for tag in card.tags.iter_mut().filter(|tag| *tag == from) {
+                    *tag = to.to_string();
+                }
+            }
+        }
+        renamed
+    }
+
+    /// Star or unstar a card. Returns `false` when the card doesn't exist.
+    pub fn set_starred(&mut self, card_id: u32, starred: bool) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.starred = starred;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark a card as mastered once it has `threshold` correct answers in a row.
+    /// Returns `true` only when this call newly masters it.
+    pub fn update_mastery(&mut self, card_id: u32, threshold: u32) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card)
+                if !card.metadata.mastered
+                    && card.metadata.consecutive_correct >= threshold.max(1) =>
+            {
+                card.metadata.mastered = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Put a mastered card back into rotation, starting its streak of correct answers over.
+    pub fn unmaster(&mut self, card_id: u32) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.metadata.mastered = false;
+                card.metadata.consecutive_correct = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Archive or unarchive a card. Returns `false` when the card doesn't exist.
+    pub fn set_archived(&mut self, card_id: u32, archived: bool) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.archived = archived;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set how heavily `quiz --smart` favors a card, clamped to 1-`MAX_WEIGHT`.
+    /// Returns the weight it was given, or `None` when the card doesn't exist.
+    pub fn set_weight(&mut self, card_id: u32, weight: u8) -> Option<u8> {
+        let card = self.cards.get_mut(&card_id)?;
+        card.metadata.weight = weight.clamp(1, MAX_WEIGHT);
+        Some(card.metadata.weight)
+    }
+
+    /// Give a card a fixed review interval, or go back to its normal schedule with `None`.
+    /// Returns `false` when the card doesn't exist.
+    pub fn set_fixed_interval(&mut self, card_id: u32, days: Option<u32>) -> bool {
+        match self.cards.get_mut(&card_id) {
+            Some(card) => {
+                card.metadata.fixed_interval_days = days;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Promote a card one Leitner box on a correct answer, or send it back to box 1.
+    pub fn move_leitner_box(&mut self, card_id: u32, correct: bool) {
+        if let Some(card) = self.cards.get_mut(&card_id) {
+            card.metadata.box_number = if correct {
+                (card.metadata.box_number + 1).min(LEITNER_BOXES)
+            } else {
+                1
+            };
+        }
+    }
+
+    /// Fold one timed answer into the card's running average response time.
+    pub fn record_response_time(&mut self, card_id: u32, elapsed: Duration) {
+        if let Some(card) = self.cards.get_mut(&card_id) {
+            let metadata = &mut card.metadata;
+            let previous =
+                metadata.avg_response_secs.unwrap_or(0.0) * metadata.timed_reviews as f64;
+            metadata.timed_reviews += 1;
+            metadata.avg_response_secs =
+                Some((previous + elapsed.as_secs_f64()) / metadata.timed_reviews as f64);
+        }
+    }
+
+    /// Number of cards in each Leitner box, starting with box 1.
+    pub fn box_counts(&self) -> [usize; LEITNER_BOXES as usize] {
+        let mut counts = [0; LEITNER_BOXES as usize];
+        for card in self.cards.values() {
+            counts[usize::from(card.metadata.box_number.clamp(1, LEITNER_BOXES)) - 1] += 1;
+        }
+        counts
+    }
+
+    pub fn delete_card(&mut self, card_id: u32) -> bool {
+        self.cards.remove(&card_id).is_some()
+    }
+
+    pub fn get_card(&self, card_id: u32) -> Option<&Flashcard> {
+        self.cards.get(&card_id)
+    }
+
+    pub fn reset_all_stats(&mut self) {
+        for card in self.cards.values_mut() {
+            card.metadata.reset_stats();
+        }
+    }
+
+    /// Case-insensitive substring search over questions and answers, sorted by ID.
+    pub fn search(&self, query: &str) -> Vec<&Flashcard> {
+        self.search_in(query, SearchScope::All, false)
+    }
+
+    pub fn search_in(
+        &self,
+        query: &str,
+        scope: SearchScope,
+        case_sensitive: bool,
+    ) -> Vec<&Flashcard> {
+        let mut matches: Vec<&Flashcard> = self
+            .cards
+            .values()
+            .filter(|card| {
+                let in_question = text_contains(&card.question, query, case_sensitive);
+                let in_answer = text_contains(&card.answer, query, case_sensitive);
+                match scope {
+                    SearchScope::All => in_question || in_answer,
+                    SearchScope::Questions => in_question,
+                    SearchScope::Answers => in_answer,
+                }
+            })
+            .collect();
+        matches.sort_by_key(|card| card.id);
+        matches
+    }
+
+    /// IDs of the cards due on or before `today`, most overdue first.
+    pub fn due_cards(&self, today: chrono::NaiveDate) -> Vec<u32> {
+        let mut due: Vec<&Flashcard> = self
+            .cards
+            .values()
+            .filter(|card| CardSelection::default().includes(card) && card.metadata.is_due(today))
+            .collect();
+        due.sort_by_key(|card| (card.metadata.effective_due_date(), card.id));
+        due.into_iter().map(|card| card.id).collect()
+    }
+
+    /// Card IDs ordered by `CardMetadata::priority`, most urgent first and ties by ID.
+    pub fn weighted_order(&self, today: chrono::NaiveDate) -> Vec<u32> {
+        let mut cards: Vec<(f64, u32)> = self
+            .cards
+            .values()
+            .map(|card| (card.metadata.priority(today), card.id))
+            .collect();
+        cards.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        cards.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Card IDs by last review, oldest first. Never-reviewed cards come before all others.
+    pub fn by_last_reviewed(&self) -> Vec<u32> {
+        let mut cards: Vec<&Flashcard> = self.cards.values().collect();
+        cards.sort_by_key(|card| (card.metadata.last_reviewed, card.id));
+        cards.into_iter().map(|card| card.id).collect()
+    }
+
+    /// Keep the score of a finished quiz as the deck's best when it beats the record.
+    /// Quizzes of fewer than `MIN_RECORD_QUIZ_CARDS` cards don't count. Returns `true` when
+    /// the score is a new record.
+    pub fn record_quiz_score(
+        &mut self,
+        correct: usize,
+        total: usize,
+        date: chrono::NaiveDate,
+    ) -> bool {
+        if total < MIN_RECORD_QUIZ_CARDS {
+            return false;
+        }
+        let percentage = correct as f64 / total as f64 * 100.0;
+        if self
+            .best_quiz_percentage
+            .is_some_and(|best| percentage <= best)
+        {
+            return false;
+        }
+        self.best_quiz_percentage = Some(percentage);
+        self.best_quiz_date = Some(date);
+        true
+    }
+
+    /// IDs of the reviewed cards whose success rate is below `threshold` percent, by ID.
+    pub fn failing_cards(&self, threshold: f64) -> Vec<u32> {
+        let mut failing: Vec<u32> = self
+            .cards
+            .values()
+            .filter(|card| {
+                card.metadata.times_reviewed > 0 && card.metadata.success_rate() < threshold
+            })
+            .map(|card| card.id)
+            .collect();
+        failing.sort_unstable();
+        failing
+    }
+
+    pub fn cards_by_difficulty(&self, difficulty: &Difficulty) -> Vec<u32> {
+        self.cards
+            .values()
+            .filter(|card| card.metadata.difficulty == *difficulty)
+            .map(|card| card.id)
+            .collect()
+    }
+
+    pub fn compute_stats(&self, include_archived: bool) -> DeckStats {
+        let cards: Vec<&Flashcard> = self
+            .cards
+            .values()
+            .filter(|card| include_archived || !card.archived)
+            .collect();
+        let mut stats = DeckStats {
+            total_cards: cards.len(),
+            best_quiz_percentage: self.best_quiz_percentage,
+            best_quiz_date: self.best_quiz_date,
+            ..DeckStats::default()
+        };
+
+        for card in &cards {
+            stats.total_reviews += card.metadata.times_reviewed;
+            stats.total_correct += card.metadata.correct_count;
+            match card.metadata.difficulty {
+                Difficulty::Easy => stats.easy_cards += 1,
+                Difficulty::Medium => stats.medium_cards += 1,
+                Difficulty::Hard => stats.hard_cards += 1,
+            }
+            if card.metadata.times_reviewed == 0 {
+                stats.never_reviewed += 1;
+            }
+        }
+
+        if stats.total_reviews > 0 {
+            stats.success_rate = (stats.total_correct as f64 / stats.total_reviews as f64) * 100.0;
+        }
+
+        // Ties go to the lowest ID so the result doesn't depend on HashMap order
+        stats.most_reviewed = cards
+            .iter()
+            .max_by_key(|card| (card.metadata.times_reviewed, std::cmp::Reverse(card.id)))
+            .map(|card| card.id);
+        stats.least_reviewed = cards
+            .iter()
+            .min_by_key(|card| (card.metadata.times_reviewed, card.id))
+            .map(|card| card.id);
+
+        stats
+    }
+
+    /// Number of reviews on each local calendar day. Cards saved before review history
+    /// was kept count once, on the day they were last reviewed.
+    pub fn review_counts_by_day(&self) -> HashMap<chrono::NaiveDate, u32> {
+        let local_day =
+            |date: &chrono::DateTime<chrono::Utc>| date.with_timezone(&chrono::Local).date_naive();
+        let mut counts = HashMap::new();
+        for card in self.cards.values() {
+            if card.metadata.history.is_empty() {
+                if let Some(date) = &card.metadata.last_reviewed {
+                    *counts.entry(local_day(date)).or_insert(0) += 1;
+                }
+            } else {
+                for event in &card.metadata.history {
+                    *counts.entry(local_day(&event.date)).or_insert(0) += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Measure question and answer lengths, to spot cards that are too long to learn well.
+    pub fn compute_length_stats(&self, include_archived: bool) -> LengthStats {
+        let cards: Vec<&Flashcard> = self
+            .cards
+            .values()
+            .filter(|card| include_archived || !card.archived)
+            .collect();
+        let questions = TextLengths::measure(cards.iter().map(|card| card.question.as_str()));
+        let answers = TextLengths::measure(cards.iter().map(|card| card.answer.as_str()));
+        LengthStats {
+            total_words: questions.total_words + answers.total_words,
+            questions,
+            answers,
+        }
+    }
+
+    /// Card count, reviews and success rate per tag. A card counts towards each of its tags.
+    pub fn compute_tag_stats(&self, include_archived: bool) -> HashMap<String, TagStats> {
+        let mut by_tag: HashMap<String, TagStats> = HashMap::new();
+        for card in self
+            .cards
+            .values()
+            .filter(|card| include_archived || !card.archived)
+        {
+            for tag in &card.tags {
+                let stats = by_tag.entry(tag.clone()).or_default();
+                stats.cards += 1;
+                stats.reviews += card.metadata.times_reviewed;
+                stats.correct += card.metadata.correct_count;
+            }
+        }
+        for stats in by_tag.values_mut() {
+            if stats.reviews > 0 {
+                stats.success_rate = (stats.correct as f64 / stats.reviews as f64) * 100.0;
+            }
+        }
+        by_tag
+    }
+
+    /// Number of reviews on the local calendar day `date`.
+    pub fn reviews_on(&self, date: chrono::NaiveDate) -> u32 {
+        self.review_counts_by_day().get(&date).copied().unwrap_or(0)
+    }
+
+    /// Count `date` towards the streak of consecutive days with at least one review.
+    pub fn record_study_day(&mut self, date: chrono::NaiveDate) {
+        match self.last_study_date {
+            Some(last) if last >= date => return,
+            Some(last) if last.succ_opt() == Some(date) => self.current_streak += 1,
+            _ => self.current_streak = 1,
+        }
+        self.last_study_date = Some(date);
+        self.longest_streak = self.longest_streak.max(self.current_streak);
+    }
+
+    /// The streak as of `today`, which is broken once a whole day passes without reviews.
+    pub fn streak_on(&self, today: chrono::NaiveDate) -> u32 {
+        match self.last_study_date {
+            Some(last) if last == today || last.succ_opt() == Some(today) => self.current_streak,
+            _ => 0,
+        }
+    }
+
+    /// Card IDs in ascending order, for working through a deck front to back.
+    pub fn get_cards_ids_in_order(&self, selection: &CardSelection) -> Vec<u32> {
+        let mut cards_ids: Vec<u32> = self
+            .cards
+            .values()
+            .filter(|card| selection.includes(card))
+            .map(|card| card.id)
+            .collect();
+        cards_ids.sort_unstable();
+        cards_ids
+    }
+
+    /// Card IDs in random order. With a seed the order is the same on every run.
+    pub fn get_random_cards_ids(&self, selection: &CardSelection, seed: Option<u64>) -> Vec<u32> {
+        // Start from a fixed order, since HashMap iteration order changes between runs
+        let mut cards_ids = self.get_cards_ids_in_order(selection);
+        match seed {
+            Some(seed) => cards_ids.shuffle(&mut StdRng::seed_from_u64(seed)),
+            None => cards_ids.shuffle(&mut rand::rng()),
+        }
+        cards_ids
+    }
+
+    pub fn export_csv(
+        &self,
+        filename: &str,
+        fields: &[CsvField],
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cards: Vec<&Flashcard> = self.cards.values().collect();
+        cards.sort_by_key(|card| card.id);
+        let fields = if fields.is_empty() {
+            &CsvField::ALL[..]
+        } else {
+            fields
+        };
+
+        let mut writer = csv::Writer::from_path(filename)?;
+        writer.write_record(fields.iter().map(|field| field.header()))?;
+        for card in &cards {
+            writer.write_record(fields.iter().map(|field| field.value(card)))?;
+        }
+        writer.flush()?;
+        Ok(cards.len())
+    }
+
+    /// Write one CSV row per review in date order, or one row per reviewed card when the
+    /// deck has no review history yet. Returns the number of rows written.
+    pub fn export_stats_csv(&self, filename: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut events: Vec<(u32, &ReviewEvent)> = self
+            .cards
+            .values()
+            .flat_map(|card| card.metadata.history.iter().map(|event| (card.id, event)))
+            .collect();
+        events.sort_by_key(|(id, event)| (event.date, *id));
+
+        let mut writer = csv::Writer::from_path(filename)?;
+        if events.is_empty() {
+            let mut cards: Vec<&Flashcard> = self
+                .cards
+                .values()
+                .filter(|card| card.metadata.times_reviewed > 0)
+                .collect();
+            cards.sort_by_key(|card| card.id);
+            for card in &cards {
+                writer.serialize(CardStatsCsvRow {
+                    last_reviewed: card
+                        .metadata
+                        .last_reviewed
+                        .as_ref()
+                        .map(format_review_date)
+                        .unwrap_or_default(),
+                    card_id: card.id,
+                    times_reviewed: card.metadata.times_reviewed,
+                    correct_count: card.metadata.correct_count,
+                    success_rate: format!("{:.1}", card.metadata.success_rate()),
+                })?;
+            }
+            writer.flush()?;
+            return Ok(cards.len());
+        }
+
+        let mut correct = 0;
+        for (reviews, (card_id, event)) in events.iter().enumerate() {
+            if event.correct {
+                correct += 1;
+            }
+            writer.serialize(ReviewCsvRow {
+                date: format_review_date(&event.date),
+                card_id: *card_id,
+                correct: event.correct,
+                success_rate: format!("{:.1}", correct as f64 / (reviews + 1) as f64 * 100.0),
+            })?;
+        }
+        writer.flush()?;
+        Ok(events.len())
+    }
+
+    /// Write the whole deck, statistics and settings included, as JSON wrapped in an envelope
+    /// with its schema version: `{ "schema": 2, "deck": {...} }`. Returns the number of cards.
+    pub fn export_json(&self, filename: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let envelope = DeckEnvelope {
+            schema: SCHEMA_VERSION,
+            deck: self,
+        };
+        fs::write(filename, serde_json::to_string_pretty(&envelope)?)?;
+        Ok(self.cards.len())
+    }
+
+    /// Write every card as a Markdown section with the answer in a collapsible block.
+    pub fn export_markdown(&self, filename: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cards: Vec<&Flashcard> = self.cards.values().collect();
+        cards.sort_by_key(|card| card.id);
+
+        let mut markdown = String::from("# Flashcards\n");
+        for card in &cards {
+            markdown.push_str(&format!(
+                "\n## {}. {}\n\n<details>\n<summary>Answer</summary>\n\n{}\n\n</details>\n\n",
+                card.id, card.question, card.answer
+            ));
+            markdown.push_str(&format!(
+                "_Difficulty: {:?} · Reviewed {} times · Success {:.0}%_\n",
+                card.metadata.difficulty,
+                card.metadata.times_reviewed,
+                card.metadata.success_rate()
+            ));
+        }
+
+        fs::write(filename, markdown)?;
+        Ok(cards.len())
+    }
+
+    /// Write a tab-separated file that Anki can import, one card per line with its tags.
+    pub fn export_anki(&self, filename: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut cards: Vec<&Flashcard> = self.cards.values().collect();
+        cards.sort_by_key(|card| card.id);
+
+        let mut lines = String::from("#separator:tab\n#html:true\n#tags column:3\n");
+        for card in &cards {
+            let tags: Vec<String> = card.tags.iter().map(|tag| tag.replace(' ', "_")).collect();
+            lines.push_str(&format!(
+                "{}\t{}\t{}\n",
+                anki_field(&card.question),
+                anki_field(&card.answer),
+                tags.join(" ")
+            ));
+        }
+        fs::write(filename, lines)?;
+        Ok(cards.len())
+    }
+
+    /// Add a new card for every row of a CSV file with `question` and `answer` columns.
+    /// `progress` is called after each row with how many bytes of the file have been read.
+    pub fn import_csv(
+        &mut self,
+        filename: &str,
+        mut progress: impl FnMut(u64),
+    ) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        let mut reader = csv::Reader::from_path(filename)?;
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|header| header.trim().eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("CSV file is missing a '{}' column", name))
+        };
+        let question_column = column("question")?;
+        let answer_column = column("answer")?;
+
+        let mut summary = ImportSummary::default();
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            progress(reader.position().byte());
+            let (Some(question), Some(answer)) =
+                (record.get(question_column), record.get(answer_column))
+            else {
+                summary.skipped += 1;
+                continue;
+            };
+            self.add_card(question.to_string(), answer.to_string());
+            summary.added += 1;
+        }
+        Ok(summary)
+    }
+
+    /// Add a new card for every `question <separator> answer` line of a text file.
+    /// `progress` is called after each line with how many bytes of the file have been read.
+    pub fn import_text(
+        &mut self,
+        filename: &str,
+        separator: &str,
+        mut progress: impl FnMut(u64),
+    ) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(filename)?;
+
+        let mut summary = ImportSummary::default();
+        let mut read = 0;
+        for line in content.split_inclusive('\n') {
+            read += line.len() as u64;
+            progress(read);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.split_once(separator) {
+                Some((question, answer))
+                    if !question.trim().is_empty() && !answer.trim().is_empty() =>
+                {
+                    self.add_card(question.trim().to_string(), answer.trim().to_string());
+                    summary.added += 1;
+                }
+                _ => summary.skipped += 1,
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Add a new card for every term/definition pair of a Quizlet export, where `term_sep`
+    /// comes between a term and its definition and `card_sep` between cards. `progress` is
+    /// called after each pair with how many bytes of the file have been read.
+    pub fn import_quizlet(
+        &mut self,
+        filename: &str,
+        term_sep: &str,
+        card_sep: &str,
+        mut progress: impl FnMut(u64),
+    ) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        if term_sep.is_empty() || card_sep.is_empty() {
+            return Err("Quizlet separators can't be empty".into());
+        }
+        let content = fs::read_to_string(filename)?;
+
+        let mut summary = ImportSummary::default();
+        let mut read = 0;
+        for pair in content.split(card_sep) {
+            read = (read + (pair.len() + card_sep.len()) as u64).min(content.len() as u64);
+            progress(read);
+            if pair.trim().is_empty() {
+                continue;
+            }
+            match pair.split_once(term_sep) {
+                Some((term, definition))
+                    if !term.trim().is_empty() && !definition.trim().is_empty() =>
+                {
+                    self.add_card(term.trim().to_string(), definition.trim().to_string());
+                    summary.added += 1;
+                }
+                _ => summary.skipped += 1,
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// Turn the `\t`, `\n` and `\\` escapes typed on the command line into the characters they
+/// stand for, so separators like a tab can be passed without a literal tab.
+pub fn unescape_separator(separator: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = separator.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The version of the deck file format, also written into `json` exports. Files from
+/// before the version was stored are version 1.
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeckCollection {
+    #[serde(default = "legacy_schema_version")]
+    pub schema_version: u32,
+    pub decks: HashMap<String, FlashcardDeck>,
+    /// The older schema version the file was migrated from when it was loaded, if any
+    #[serde(skip)]
+    pub migrated_from: Option<u32>,
+}
+
+fn legacy_schema_version() -> u32 {
+    1
+}
+
+impl Default for DeckCollection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeckCollection {
+    pub fn new() -> Self {
+        DeckCollection {
+            schema_version: SCHEMA_VERSION,
+            decks: HashMap::new(),
+            migrated_from: None,
+        }
+    }
+
+    /// Save the collection, keeping the previous contents of the file as a single-level backup.
+    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let json_data = serde_json::to_string_pretty(self)?;
+
+        if let Some(dir) = std::path::Path::new(filename).parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        if std::path::Path::new(filename).exists() {
+            // Copy first and rename into place so the old backup survives a crash mid-copy
+            let backup = Self::backup_path(filename);
+            let partial_backup = format!("{}.tmp", backup);
+            fs::copy(filename, &partial_backup)?;
+            fs::rename(&partial_backup, &backup)?;
+        }
+
+        // Write the new contents next to the file and rename them over it, so an interrupted
+        // save leaves either the old file or the new one, never a truncated mix
+        let partial = format!("{}.tmp", filename);
+        let written = fs::File::create(&partial).and_then(|mut file| {
+            file.write_all(json_data.as_bytes())?;
+            file.sync_all()
+        });
+        if let Err(error) = written.and_then(|_| fs::rename(&partial, filename)) {
+            let _ = fs::remove_file(&partial);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+
+    pub fn backup_path(filename: &str) -> String {
+        format!("{}.bak", filename)
+    }
+
+    /// Whether a deck file is read as JSON5, which allows comments and trailing commas.
+    pub fn is_json5(filename: &str) -> bool {
+        std::path::Path::new(filename)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json5"))
+    }
+
+    /// Load a collection, migrating files that hold a single deck into the default deck.
+    /// A deck in a `json` export envelope also lands in the default deck, and records the
+    /// envelope's schema version. `.json5` files are parsed as JSON5; they are still saved
+    /// as standard JSON.
+    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file_content = fs::read_to_string(filename)?;
+        let value: serde_json::Value = if Self::is_json5(filename) {
+            json5::from_str(&file_content)?
+        } else {
+            serde_json::from_str(&file_content)?
+        };
+        Self::from_value(value)
+    }
+
+    /// Build a collection from a parsed file in any of the shapes `load_from_file` accepts,
+    /// migrating it to the current schema version first.
+    fn from_value(mut value: serde_json::Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let (mut value, version) = if value.get("decks").is_some() {
+            let version = match value.get("schema_version") {
+                Some(version) => schema_number(version)?,
+                None => legacy_schema_version(),
+            };
+            (value, version)
+        } else if let Some(schema) = value.get("schema") {
+            let version = schema_number(schema)?;
+            let deck = value
+                .get_mut("deck")
+                .map(serde_json::Value::take)
+                .ok_or("the export has a schema version but no deck")?;
+            (
+                serde_json::json!({ "decks": { DEFAULT_DECK: deck } }),
+                version,
+            )
+        } else {
+            let version = legacy_schema_version();
+            (
+                serde_json::json!({ "decks": { DEFAULT_DECK: value } }),
+                version,
+            )
+        };
+        if version > SCHEMA_VERSION {
+            return Err(format!(
+                "schema version {} is newer than this version supports ({})",
+                version, SCHEMA_VERSION
+            )
+            .into());
+        }
+
+        migrate(&mut value, version);
+        let mut collection: DeckCollection = serde_json::from_value(value)?;
+        collection.schema_version = SCHEMA_VERSION;
+        collection.migrated_from = Some(version).filter(|version| *version < SCHEMA_VERSION);
+        Ok(collection)
+    }
+}
+
+/// How the time a snapshot was taken is written in its name.
+const SNAPSHOT_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+
+/// What comes before and after the timestamp in the names of a deck file's snapshots.
+/// Snapshots keep the deck file's extension, so `flashcards.json` and `flashcards.json5`
+/// get `flashcards-<timestamp>.json` and `flashcards-<timestamp>.json5` respectively.
+fn snapshot_affixes(deck_file: &str) -> (String, String) {
+    let path = std::path::Path::new(deck_file);
+    let stem = path
+        .file_stem()
+        .map_or("flashcards".into(), |stem| stem.to_string_lossy());
+    let extension = path.extension().map_or(String::new(), |extension| {
+        format!(".{}", extension.to_string_lossy())
+    });
+    (format!("{}-", stem), extension)
+}
+
+/// Whether `name` is the name of one of the deck file's snapshots.
+fn is_snapshot_of(name: &str, deck_file: &str) -> bool {
+    let (prefix, suffix) = snapshot_affixes(deck_file);
+    name.strip_prefix(&prefix)
+        .and_then(|rest| rest.strip_suffix(&suffix))
+        .is_some_and(|timestamp| {
+            chrono::NaiveDateTime::parse_from_str(timestamp, SNAPSHOT_TIMESTAMP).is_ok()
+        })
+}
+
+/// The names of a deck file's snapshots in `dir`, oldest first.
+pub fn list_snapshots(dir: &std::path::Path, deck_file: &str) -> std::io::Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if is_snapshot_of(&name, deck_file) {
+            names.push(name);
+        }
+    }
+    // The timestamps sort chronologically
+    names.sort();
+    Ok(names)
+}
+
+/// Copy the deck file into `dir` as a snapshot, returning the snapshot's path.
+pub fn create_snapshot(
+    dir: &std::path::Path,
+    deck_file: &str,
+) -> std::io::Result<std::path::PathBuf> {
+    fs::create_dir_all(dir)?;
+    let (prefix, suffix) = snapshot_affixes(deck_file);
+    let path = dir.join(format!(
+        "{}{}{}",
+        prefix,
+        chrono::Local::now().format(SNAPSHOT_TIMESTAMP),
+        suffix
+    ));
+    fs::copy(deck_file, &path)?;
+    Ok(path)
+}
+
+/// Delete the oldest snapshots in `dir` so that at most `keep` remain. Returns how many were
+/// deleted.
+pub fn prune_snapshots(
+    dir: &std::path::Path,
+    deck_file: &str,
+    keep: usize,
+) -> std::io::Result<usize> {
+    let names = list_snapshots(dir, deck_file)?;
+    let excess = names.len().saturating_sub(keep);
+    for name in &names[..excess] {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(excess)
+}
+
+fn schema_number(value: &serde_json::Value) -> Result<u32, &'static str> {
+    value
+        .as_u64()
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or("the schema version must be a whole number")
+}
+
+/// Upgrade a parsed collection from schema version `from` to `SCHEMA_VERSION`, one version
+/// at a time. Fields added since then are filled in by their serde defaults.
+fn migrate(value: &mut serde_json::Value, from: u32) {
+    if from < 2 {
+        // Version 1 files may store review times as plain `%Y-%m-%d` dates
+        let cards = value
+            .get_mut("decks")
+            .and_then(serde_json::Value::as_object_mut)
+            .into_iter()
+            .flat_map(|decks| decks.values_mut())
+            .filter_map(|deck| {
+                deck.get_mut("cards")
+                    .and_then(serde_json::Value::as_object_mut)
+            })
+            .flat_map(|cards| cards.values_mut());
+        for card in cards {
+            if let Some(last_reviewed) = card.pointer_mut("/metadata/last_reviewed")
+                && let Some(date) = last_reviewed
+                    .as_str()
+                    .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            {
+                let date_time = date.and_time(chrono::NaiveTime::MIN).and_utc();
+                *last_reviewed = serde_json::Value::String(date_time.to_rfc3339());
+            }
+        }
+    }
+}
+
+impl Default for FlashcardDeck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for CardMetadata {
+    fn default() -> Self {
+        CardMetadata {
+            difficulty: Difficulty::Medium,
+            times_reviewed: 0,
+            correct_count: 0,
+            last_reviewed: None,
+            ease_factor: default_ease_factor(),
+            interval_days: 0,
+            repetitions: 0,
+            due_date: None,
+            box_number: default_box_number(),
+            avg_response_secs: None,
+            timed_reviews: 0,
+            history: Vec::new(),
+            consecutive_correct: 0,
+            mastered: false,
+            fixed_interval_days: None,
+            weight: default_weight(),
+            reverse: DirectionStats::default(),
+        }
+    }
+}
+
+/// Quizzes need at least this many cards for their score to count as the deck's best.
+pub const MIN_RECORD_QUIZ_CARDS: usize = 5;
+
+/// The cards `seed` adds so a new deck has something to quiz.
+pub const SAMPLE_CARDS: [(&str, &str); 6] = [
+    ("What is the capital of France?", "Paris"),
+    ("What is the chemical symbol for gold?", "Au"),
+    ("How many continents are there?", "7"),
+    ("Who wrote \"Romeo and Juliet\"?", "William Shakespeare"),
+    ("What is the largest planet in the solar system?", "Jupiter"),
+    ("How do you say \"thank you\" in Spanish?", "Gracias"),
+];
+/// Sample cards are tagged with this so they are easy to find and remove.
+pub const SAMPLE_TAG: &str = "sample";
+
+/// Answers longer than this many characters are flagged by `lint`.
+pub const LINT_LONG_ANSWER_CHARS: usize = 300;
+/// `add` warns about, or with `--strict` rejects, answers longer than this unless configured.
+pub const DEFAULT_MAX_ANSWER_CHARS: usize = 500;
+/// Cards reviewed at least this often without ever being right are flagged by `lint`.
+pub const LINT_FAILING_REVIEWS: u32 = 5;
+
+/// One deck-quality check run by `lint`.
+pub struct LintCheck {
+    pub description: &'static str,
+    /// Serious problems make `lint` exit with an error.
+    pub serious: bool,
+    pub find: fn(&FlashcardDeck) -> Vec<u32>,
+}
+
+pub const LINT_CHECKS: [LintCheck; 6] = [
+    LintCheck {
+        description: "Empty question or answer",
+        serious: true,
+        find: lint_empty_fields,
+    },
+    LintCheck {
+        description: "Answer repeats the question",
+        serious: true,
+        find: lint_answer_repeats_question,
+    },
+    LintCheck {
+        description: "Duplicate questions",
+        serious: true,
+        find: lint_duplicate_questions,
+    },
+    LintCheck {
+        description: "Very long answers",
+        serious: false,
+        find: lint_long_answers,
+    },
+    LintCheck {
+        description: "Never reviewed",
+        serious: false,
+        find: lint_never_reviewed,
+    },
+    LintCheck {
+        description: "Always answered wrong",
+        serious: false,
+        find: lint_always_failing,
+    },
+];
+
+/// The IDs of the deck's cards matching `predicate`, in ID order.
+fn lint_cards(deck: &FlashcardDeck, predicate: impl Fn(&Flashcard) -> bool) -> Vec<u32> {
+    let mut ids: Vec<u32> = deck
+        .cards
+        .values()
+        .filter(|card| predicate(card))
+        .map(|card| card.id)
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+pub fn lint_empty_fields(deck: &FlashcardDeck) -> Vec<u32> {
+    lint_cards(deck, |card| {
+        card.question.trim().is_empty()
+            || (card.kind == CardKind::Basic && card.answer.trim().is_empty())
+    })
+}
+
+pub fn lint_answer_repeats_question(deck: &FlashcardDeck) -> Vec<u32> {
+    lint_cards(deck, Flashcard::answer_repeats_question)
+}
+
+/// Every card whose question is shared with another card.
+pub fn lint_duplicate_questions(deck: &FlashcardDeck) -> Vec<u32> {
+    let mut ids: Vec<u32> = deck
+        .duplicate_groups()
+        .into_iter()
+        .flat_map(|group| std::iter::once(group.keep).chain(group.duplicates))
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+pub fn lint_long_answers(deck: &FlashcardDeck) -> Vec<u32> {
+    lint_cards(deck, |card| card.answer_longer_than(LINT_LONG_ANSWER_CHARS))
+}
+
+pub fn lint_never_reviewed(deck: &FlashcardDeck) -> Vec<u32> {
+    lint_cards(deck, |card| card.metadata.times_reviewed == 0)
+}
+
+pub fn lint_always_failing(deck: &FlashcardDeck) -> Vec<u32> {
+    lint_cards(deck, |card| {
+        card.metadata.times_reviewed >= LINT_FAILING_REVIEWS && card.metadata.correct_count == 0
+    })
+}
+
+/// Escape text for an Anki field: it's read as HTML, and tabs and newlines would split the line.
+fn anki_field(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', " ")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// The Monday a heatmap of `weeks` weeks starts on, so that it ends with the week of `today`.
+pub fn heatmap_start(today: chrono::NaiveDate, weeks: u32) -> chrono::NaiveDate {
+    use chrono::Datelike;
+
+    let this_monday = today - chrono::Days::new(today.weekday().num_days_from_monday().into());
+    this_monday
+        .checked_sub_days(chrono::Days::new(7 * u64::from(weeks.max(1) - 1)))
+        .unwrap_or(chrono::NaiveDate::MIN)
+}
+
+/// Review counts laid out for a heatmap: one row per weekday from Monday, one column per
+/// week from `heatmap_start`, and `None` for the days after `today`.
+pub fn heatmap_rows(
+    counts: &HashMap<chrono::NaiveDate, u32>,
+    today: chrono::NaiveDate,
+    weeks: u32,
+) -> [Vec<Option<u32>>; 7] {
+    let start = heatmap_start(today, weeks);
+    std::array::from_fn(|weekday| {
+        (0..weeks.max(1))
+            .map(|week| {
+                let day = days_after(start, week * 7 + weekday as u32);
+                (day <= today).then(|| counts.get(&day).copied().unwrap_or(0))
+            })
+            .collect()
+    })
+}
+
+/// How the user rated a single card during a quiz.
+pub struct Rating {
+    pub difficulty: Difficulty,
+    pub quality: u8,
+}
+
+impl Rating {
+    pub fn new(difficulty: Difficulty, quality: u8) -> Self {
+        Rating {
+            difficulty,
+            quality,
+        }
+    }
+
+    /// A rating from a SuperMemo-style 0-5 quality score.
+    pub fn from_quality(quality: u8) -> Self {
+        let difficulty = match quality {
+            5 => Difficulty::Easy,
+            3 | 4 => Difficulty::Medium,
+            _ => Difficulty::Hard,
+        };
+        Rating::new(difficulty, quality)
+    }
+
+    pub fn is_correct(&self) -> bool {
+        self.quality >= 3
+    }
+}
+
+/// Record a review of a card and reschedule it with either SM-2 or the Leitner boxes.
+/// With `gradual`, the difficulty moves one step instead of taking the rating's.
+/// Returns `true` when the review masters the card.
+pub fn rate_card(
+    deck: &mut FlashcardDeck,
+    card_id: u32,
+    rating: &Rating,
+    reversed: bool,
+    leitner: bool,
+    gradual: bool,
+    mastery_threshold: u32,
+) -> bool {
+    let current = &deck.cards[&card_id].metadata.difficulty;
+    let difficulty = match (gradual, rating.is_correct()) {
+        (false, _) => rating.difficulty.clone(),
+        (true, true) => current.easier(),
+        (true, false) => current.harder(),
+    };
+    deck.update_card_difficulty(
+        card_id,
+        difficulty,
+        rating.is_correct(),
+        rating.quality,
+        reversed,
+    );
+    if leitner {
+        deck.move_leitner_box(card_id, rating.is_correct());
+    } else {
+        deck.schedule_card(card_id, rating.quality, reversed);
+    }
+    deck.update_mastery(card_id, mastery_threshold)
+}
+
+/// Compare a typed answer against the expected one, ignoring surrounding whitespace and,
+/// with `ignore_accents`, diacritics.
+pub fn check_answer(expected: &str, given: &str, ignore_accents: bool) -> bool {
+    comparable_answer(expected, ignore_accents) == comparable_answer(given, ignore_accents)
+}
+
+/// Compare a typed answer case-insensitively, tolerating up to `max_distance` edits.
+pub fn check_answer_fuzzy(
+    expected: &str,
+    given: &str,
+    max_distance: usize,
+    ignore_accents: bool,
+) -> bool {
+    let expected = comparable_answer(expected, ignore_accents).to_lowercase();
+    let given = comparable_answer(given, ignore_accents).to_lowercase();
+    levenshtein(&expected, &given) <= max_distance
+}
+
+/// Trim an answer for comparison, and with `ignore_accents` drop its diacritics, so "Café"
+/// and "naïve" become "Cafe" and "naive".
+fn comparable_answer(answer: &str, ignore_accents: bool) -> String {
+    let answer = answer.trim();
+    if !ignore_accents {
+        return answer.to_string();
+    }
+    answer
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .nfc()
+        .collect()
+}
+
+/// Number of single-character insertions, deletions and substitutions between two strings.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The first letter of an answer with the rest blanked out, like `P____ (5)`.
+pub fn hint_for(answer: &str) -> String {
+    let answer = answer.trim();
+    let masked: String = answer
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            if index == 0 || c.is_whitespace() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{} ({})", masked, answer.chars().count())
+}
+
+/// Whether an answer took longer than the per-card time limit, if there is one.
+pub fn exceeds_time_limit(elapsed: Duration, limit: Option<Duration>) -> bool {
+    limit.is_some_and(|limit| elapsed > limit)
+}
+
+/// Sample up to `count` wrong options from the other cards, never repeating the right answer.
+pub fn pick_distractors(
+    deck: &FlashcardDeck,
+    card_id: u32,
+    reversed: bool,
+    count: usize,
+) -> Vec<String> {
+    let side = |card: &Flashcard| {
+        if reversed {
+            card.question.clone()
+        } else {
+            card.answer.clone()
+        }
+    };
+    let correct = side(&deck.cards[&card_id]);
+
+    let mut distractors: Vec<String> = Vec::new();
+    for card in deck.cards.values() {
+        let option = side(card);
+        if card.id != card_id && option != correct && !distractors.contains(&option) {
+            distractors.push(option);
+        }
+    }
+    distractors.shuffle(&mut rand::rng());
+    distractors.truncate(count);
+    distractors
+}
+
+/// Where a quiz left off, saved when the user quits so it can be resumed later.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuizSession {
+    pub deck: String,
+    pub remaining: Vec<u32>,
+    pub session_len: usize,
+    pub answered: usize,
+    pub correct_count: usize,
+}
+
+impl QuizSession {
+    pub fn new(deck: &str, cards: Vec<u32>) -> Self {
+        QuizSession {
+            deck: deck.to_string(),
+            session_len: cards.len(),
+            remaining: cards,
+            answered: 0,
+            correct_count: 0,
+        }
+    }
+
+    /// The session file lives next to the deck file and is named after it, so decks in the
+    /// same directory each keep their own, like `.flashcards.json.quiz-session.json`.
+    pub fn path(deck_file: &str) -> std::path::PathBuf {
+        let deck_path = std::path::Path::new(deck_file);
+        let name = deck_path
+            .file_name()
+            .map_or(DECK_FILE_NAME.into(), |name| name.to_string_lossy());
+        deck_path.with_file_name(format!(".{}.quiz-session.json", name))
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn deck_with(questions: &[(&str, &str)]) -> FlashcardDeck {
+        let mut deck = FlashcardDeck::new();
+        for (question, answer) in questions {
+            deck.add_card(question.to_string(), answer.to_string());
+        }
+        deck
+    }
+
+    #[test]
+    fn schedule_card_grows_interval_by_sm2_sequence() {
+        let mut deck = deck_with(&[("Q", "A")]);
+
+        deck.schedule_card(1, 4, false);
+        assert_eq!(deck.cards[&1].metadata.interval_days, 1);
+        deck.schedule_card(1, 4, false);
+        assert_eq!(deck.cards[&1].metadata.interval_days, 6);
+
+        let ease_factor = deck.cards[&1].metadata.ease_factor;
+        deck.schedule_card(1, 4, false);
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.interval_days, (6.0 * ease_factor).round() as u32);
+        assert_eq!(metadata.repetitions, 3);
+        assert_eq!(
+            metadata.due_date,
+            Some(today() + chrono::Days::new(metadata.interval_days.into()))
+        );
+    }
+
+    #[test]
+    fn schedule_card_resets_on_a_failed_review() {
+        let mut deck = deck_with(&[("Q", "A")]);
+        deck.schedule_card(1, 5, false);
+        deck.schedule_card(1, 5, false);
+        deck.schedule_card(1, 1, false);
+
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.interval_days, 1);
+        assert_eq!(metadata.repetitions, 0);
+        assert!(metadata.ease_factor >= 1.3);
+    }
+
+    #[test]
+    fn schedule_card_caps_the_interval_of_a_long_run_of_perfect_reviews() {
+        let mut deck = deck_with(&[("Q", "A")]);
+        for _ in 0..100 {
+            deck.schedule_card(1, 5, false);
+        }
+
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.interval_days, MAX_INTERVAL_DAYS);
+        assert_eq!(
+            metadata.due_date,
+            Some(days_after(today(), MAX_INTERVAL_DAYS))
+        );
+        assert_eq!(
+            days_after(chrono::NaiveDate::MAX, 1),
+            chrono::NaiveDate::MAX
+        );
+    }
+
+    #[test]
+    fn search_matches_questions_and_answers_ignoring_case() {
+        let deck = deck_with(&[
+            ("Capital of France?", "Paris"),
+            ("Largest planet?", "Jupiter"),
+            ("Where is the Louvre?", "In paris"),
+        ]);
+
+        let ids = |cards: Vec<&Flashcard>| cards.iter().map(|card| card.id).collect::<Vec<_>>();
+        assert_eq!(ids(deck.search("PARIS")), vec![1, 3]);
+        assert_eq!(
+            ids(deck.search_in("france", SearchScope::Questions, false)),
+            vec![1]
+        );
+        assert_eq!(
+            ids(deck.search_in("france", SearchScope::Answers, false)),
+            Vec::<u32>::new()
+        );
+        assert!(deck.search("Mars").is_empty());
+    }
+
+    fn review(deck: &mut FlashcardDeck, card_id: u32, correct: bool) {
+        let (difficulty, quality) = if correct {
+            (Difficulty::Easy, 5)
+        } else {
+            (Difficulty::Hard, 1)
+        };
+        deck.update_card_difficulty(card_id, difficulty, correct, quality, false);
+    }
+
+    #[test]
+    fn compute_stats_aggregates_the_deck() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, false);
+        review(&mut deck, 2, false);
+
+        let stats = deck.compute_stats(false);
+        assert_eq!(stats.total_cards, 3);
+        assert_eq!(stats.total_reviews, 4);
+        assert_eq!(stats.total_correct, 2);
+        assert_eq!(stats.success_rate, 50.0);
+        assert_eq!(
+            (stats.easy_cards, stats.medium_cards, stats.hard_cards),
+            (0, 1, 2)
+        );
+        assert_eq!(stats.never_reviewed, 1);
+        assert_eq!(stats.most_reviewed, Some(1));
+        assert_eq!(stats.least_reviewed, Some(3));
+    }
+
+    #[test]
+    fn compute_stats_of_an_empty_deck() {
+        let stats = FlashcardDeck::new().compute_stats(false);
+        assert_eq!(stats.total_cards, 0);
+        assert_eq!(stats.success_rate, 0.0);
+        assert_eq!(stats.most_reviewed, None);
+    }
+
+    #[test]
+    fn find_duplicate_ignores_case_and_surrounding_whitespace() {
+        let deck = deck_with(&[
+            ("Capital of France?", "Paris"),
+            ("Largest planet?", "Jupiter"),
+        ]);
+        assert_eq!(deck.find_duplicate("  capital of FRANCE?  "), Some(1));
+        assert_eq!(deck.find_duplicate("Capital of Spain?"), None);
+    }
+
+    #[test]
+    fn duplicate_groups_keep_the_most_reviewed_card() {
+        let mut deck = deck_with(&[
+            ("Capital of France?", "Paris"),
+            ("capital of france? ", "Paris"),
+            ("Largest planet?", "Jupiter"),
+            ("CAPITAL OF FRANCE?", "Paris"),
+        ]);
+        review(&mut deck, 2, true);
+
+        assert_eq!(
+            deck.duplicate_groups(),
+            vec![DuplicateGroup {
+                keep: 2,
+                duplicates: vec![1, 4],
+            }]
+        );
+    }
+
+    #[test]
+    fn merge_duplicates_folds_review_counts_into_the_kept_card() {
+        let mut deck = deck_with(&[("Q", "A"), ("q", "A"), ("Other", "B")]);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, true);
+        review(&mut deck, 2, false);
+
+        let groups = deck.duplicate_groups();
+        assert_eq!(deck.merge_duplicates(&groups), 1);
+
+        assert_eq!(deck.cards.len(), 2);
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.times_reviewed, 3);
+        assert_eq!(metadata.correct_count, 2);
+    }
+
+    fn date(text: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn due_cards_are_sorted_most_overdue_first() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3"), ("Q4", "A4")]);
+        let today = date("2024-03-10");
+        deck.cards.get_mut(&1).unwrap().metadata.due_date = Some(date("2024-03-05"));
+        deck.cards.get_mut(&3).unwrap().metadata.due_date = Some(date("2024-03-11"));
+        deck.cards.get_mut(&4).unwrap().metadata.due_date = Some(today);
+
+        // Card 2 was never scheduled, so it counts as the most overdue
+        assert_eq!(deck.due_cards(today), vec![2, 1, 4]);
+    }
+
+    #[test]
+    fn record_study_day_counts_consecutive_days_once_each() {
+        let mut deck = FlashcardDeck::new();
+        deck.record_study_day(date("2024-03-01"));
+        deck.record_study_day(date("2024-03-02"));
+        deck.record_study_day(date("2024-03-02"));
+        deck.record_study_day(date("2024-03-03"));
+
+        assert_eq!(deck.current_streak, 3);
+        assert_eq!(deck.longest_streak, 3);
+        assert_eq!(deck.last_study_date, Some(date("2024-03-03")));
+    }
+
+    #[test]
+    fn record_study_day_resets_after_a_gap() {
+        let mut deck = FlashcardDeck::new();
+        deck.record_study_day(date("2024-03-01"));
+        deck.record_study_day(date("2024-03-02"));
+        deck.record_study_day(date("2024-03-05"));
+
+        assert_eq!(deck.current_streak, 1);
+        assert_eq!(deck.longest_streak, 2);
+    }
+
+    #[test]
+    fn streak_on_breaks_once_a_whole_day_is_missed() {
+        let mut deck = FlashcardDeck::new();
+        deck.record_study_day(date("2024-03-01"));
+        deck.record_study_day(date("2024-03-02"));
+
+        assert_eq!(deck.streak_on(date("2024-03-02")), 2);
+        assert_eq!(deck.streak_on(date("2024-03-03")), 2);
+        assert_eq!(deck.streak_on(date("2024-03-04")), 0);
+    }
+
+    #[test]
+    fn record_response_time_keeps_a_running_average() {
+        let mut deck = deck_with(&[("Q", "A")]);
+        deck.record_response_time(1, Duration::from_secs(2));
+        deck.record_response_time(1, Duration::from_secs(4));
+        deck.record_response_time(1, Duration::from_secs(9));
+
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.timed_reviews, 3);
+        assert_eq!(metadata.avg_response_secs, Some(5.0));
+    }
+
+    #[test]
+    fn get_random_cards_ids_is_reproducible_with_a_seed() {
+        let deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3"), ("Q4", "A4")]);
+        let selection = CardSelection::default();
+
+        let first = deck.get_random_cards_ids(&selection, Some(42));
+        assert_eq!(first, deck.get_random_cards_ids(&selection, Some(42)));
+        let mut sorted = first.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn merge_duplicates_keeps_the_review_history() {
+        let mut deck = deck_with(&[("Q", "A"), ("q", "A")]);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, true);
+        review(&mut deck, 2, false);
+        deck.record_response_time(1, Duration::from_secs(2));
+        deck.record_response_time(2, Duration::from_secs(8));
+
+        let groups = deck.duplicate_groups();
+        deck.merge_duplicates(&groups);
+
+        let metadata = &deck.cards[&1].metadata;
+        assert_eq!(metadata.history.len(), metadata.times_reviewed as usize);
+        assert_eq!(
+            metadata
+                .history
+                .iter()
+                .filter(|event| event.correct)
+                .count(),
+            metadata.correct_count as usize
+        );
+        assert_eq!(metadata.avg_response_secs, Some(5.0));
+    }
+
+    #[test]
+    fn add_card_never_overwrites_a_card_after_a_stale_next_id() {
+        let deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        let mut value = serde_json::to_value(&deck).unwrap();
+        value["next_id"] = serde_json::json!(2);
+
+        let mut deck: FlashcardDeck = serde_json::from_value(value).unwrap();
+        let id = deck.add_card("Q4".to_string(), "A4".to_string());
+
+        assert_eq!(id, 4);
+        assert_eq!(deck.cards[&2].question, "Q2");
+        assert_eq!(deck.cards.len(), 4);
+        assert_eq!(deck.next_id, 5);
+    }
+
+    #[test]
+    fn weighted_order_puts_new_then_weak_cards_first() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        review(&mut deck, 2, true);
+        review(&mut deck, 3, false);
+        review(&mut deck, 3, true);
+
+        let today = chrono::Utc::now().date_naive();
+        assert_eq!(deck.weighted_order(today), vec![1, 3, 2]);
+    }
+
+    /// A fresh directory under the system temp dir for one test.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("flashcards-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn save_to_file_replaces_an_existing_file_and_keeps_a_backup() {
+        let dir = temp_dir("save");
+        let path = dir.join("flashcards.json");
+        let filename = path.to_str().unwrap();
+        fs::write(&path, "previous contents").unwrap();
+
+        let mut collection = DeckCollection::new();
+        collection
+            .decks
+            .insert(DEFAULT_DECK.to_string(), deck_with(&[("Q", "A")]));
+        collection.save_to_file(filename).unwrap();
+
+        let saved = DeckCollection::load_from_file(filename).unwrap();
+        assert_eq!(saved.decks[DEFAULT_DECK].cards[&1].question, "Q");
+        assert_eq!(
+            fs::read_to_string(DeckCollection::backup_path(filename)).unwrap(),
+            "previous contents"
+        );
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rename_tag_merges_into_an_existing_tag() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        deck.update_card_tags(1, vec!["spanish".to_string()], Vec::new());
+        deck.update_card_tags(2, vec!["spanish".to_string(), "es".to_string()], Vec::new());
+        deck.update_card_tags(3, vec!["verbs".to_string()], Vec::new());
+
+        assert_eq!(deck.rename_tag("spanish", "es"), 2);
+        assert_eq!(deck.cards[&1].tags, vec!["es"]);
+        assert_eq!(deck.cards[&2].tags, vec!["es"]);
+        assert_eq!(deck.cards[&3].tags, vec!["verbs"]);
+        assert_eq!(deck.rename_tag("missing", "es"), 0);
+    }
+
+    #[test]
+    fn trend_compares_recent_reviews_with_the_overall_rate() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        for correct in [
+            false, false, false, false, false, true, true, true, true, true,
+        ] {
+            review(&mut deck, 1, correct);
+        }
+        for correct in [
+            true, true, true, true, true, false, false, false, false, false,
+        ] {
+            review(&mut deck, 2, correct);
+        }
+        for _ in 0..TREND_WINDOW {
+            review(&mut deck, 3, true);
+        }
+
+        assert_eq!(deck.cards[&1].metadata.trend(), Some(Trend::Improving));
+        assert_eq!(deck.cards[&2].metadata.trend(), Some(Trend::Declining));
+        assert_eq!(deck.cards[&3].metadata.trend(), None);
+        review(&mut deck, 3, true);
+        assert_eq!(deck.cards[&3].metadata.trend(), Some(Trend::Steady));
+    }
+
+    #[test]
+    fn matches_filters_by_tag_and_difficulty() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2")]);
+        deck.update_card_tags(1, vec!["spanish".to_string()], Vec::new());
+        review(&mut deck, 1, false);
+        let card = &deck.cards[&1];
+
+        assert!(card.matches(None, None));
+        assert!(card.matches(Some("spanish"), None));
+        assert!(card.matches(Some(" Spanish "), Some(&Difficulty::Hard)));
+        assert!(!card.matches(Some("spanish"), Some(&Difficulty::Easy)));
+        assert!(!card.matches(Some("french"), None));
+        assert!(!deck.cards[&2].matches(Some("spanish"), None));
+    }
+
+    #[test]
+    fn by_last_reviewed_puts_never_reviewed_cards_first() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3"), ("Q4", "A4")]);
+        let reviewed_at = |text: &str| Some(date(text).and_time(chrono::NaiveTime::MIN).and_utc());
+        deck.cards.get_mut(&1).unwrap().metadata.last_reviewed = reviewed_at("2024-03-05");
+        deck.cards.get_mut(&2).unwrap().metadata.last_reviewed = reviewed_at("2024-01-20");
+        deck.cards.get_mut(&4).unwrap().metadata.last_reviewed = reviewed_at("2024-02-11");
+
+        assert_eq!(deck.by_last_reviewed(), vec![3, 2, 4, 1]);
+    }
+
+    #[test]
+    fn consecutive_correct_answers_master_a_card_and_a_wrong_one_resets() {
+        let mut deck = deck_with(&[("Q", "A")]);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, true);
+        review(&mut deck, 1, false);
+        assert_eq!(deck.cards[&1].metadata.consecutive_correct, 0);
+        assert!(!deck.update_mastery(1, 3));
+
+        for _ in 0..3 {
+            review(&mut deck, 1, true);
+        }
+        assert!(deck.update_mastery(1, 3));
+        assert!(!deck.update_mastery(1, 3));
+        assert!(deck.cards[&1].metadata.mastered);
+        assert!(!CardSelection::default().includes(&deck.cards[&1]));
+
+        assert!(deck.unmaster(1));
+        assert!(!deck.cards[&1].metadata.mastered);
+        assert_eq!(deck.cards[&1].metadata.consecutive_correct, 0);
+    }
+
+    #[test]
+    fn lint_checks_find_the_offending_cards() {
+        let long_answer = "x".repeat(LINT_LONG_ANSWER_CHARS + 1);
+        let mut deck = deck_with(&[
+            ("Empty answer?", " "),
+            ("Paris", "paris"),
+            ("Capital of France?", "Paris"),
+            ("capital of france?", "Paris"),
+            ("Long?", &long_answer),
+            ("Hard one?", "Yes"),
+        ]);
+        for _ in 0..LINT_FAILING_REVIEWS {
+            review(&mut deck, 6, false);
+        }
+
+        assert_eq!(lint_empty_fields(&deck), vec![1]);
+        assert_eq!(lint_answer_repeats_question(&deck), vec![2]);
+        assert_eq!(lint_duplicate_questions(&deck), vec![3, 4]);
+        assert_eq!(lint_long_answers(&deck), vec![5]);
+        assert_eq!(lint_never_reviewed(&deck), vec![1, 2, 3, 4, 5]);
+        assert_eq!(lint_always_failing(&deck), vec![6]);
+    }
+
+    #[test]
+    fn humanize_since_uses_the_largest_whole_unit() {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-06-15T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let ago = |delta: chrono::TimeDelta| humanize_since(now - delta, now);
+
+        assert_eq!(ago(chrono::TimeDelta::seconds(30)), "just now");
+        assert_eq!(ago(chrono::TimeDelta::minutes(1)), "1 minute ago");
+        assert_eq!(ago(chrono::TimeDelta::hours(5)), "5 hours ago");
+        assert_eq!(ago(chrono::TimeDelta::days(3)), "3 days ago");
+        assert_eq!(ago(chrono::TimeDelta::days(14)), "2 weeks ago");
+        assert_eq!(ago(chrono::TimeDelta::days(65)), "2 months ago");
+        assert_eq!(ago(chrono::TimeDelta::days(400)), "1 year ago");
+    }
+
+    #[test]
+    fn compute_tag_stats_counts_a_card_towards_each_tag() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        deck.update_card_tags(
+            1,
+            vec!["spanish".to_string(), "verbs".to_string()],
+            Vec::new(),
+        );
+        deck.update_card_tags(2, vec!["spanish".to_string()], Vec::new());
+        review(&mut deck, 1, true);
+        review(&mut deck, 2, false);
+        review(&mut deck, 2, true);
+
+        let by_tag = deck.compute_tag_stats(false);
+        assert_eq!(by_tag.len(), 2);
+        let spanish = &by_tag["spanish"];
+        assert_eq!((spanish.cards, spanish.reviews, spanish.correct), (2, 3, 2));
+        let verbs = &by_tag["verbs"];
+        assert_eq!((verbs.cards, verbs.reviews, verbs.correct), (1, 1, 1));
+        assert_eq!(verbs.success_rate, 100.0);
+    }
+
+    #[test]
+    fn difficulty_steps_stop_at_the_ends() {
+        assert_eq!(Difficulty::Hard.easier(), Difficulty::Medium);
+        assert_eq!(Difficulty::Medium.easier(), Difficulty::Easy);
+        assert_eq!(Difficulty::Easy.easier(), Difficulty::Easy);
+        assert_eq!(Difficulty::Easy.harder(), Difficulty::Medium);
+        assert_eq!(Difficulty::Medium.harder(), Difficulty::Hard);
+        assert_eq!(Difficulty::Hard.harder(), Difficulty::Hard);
+    }
+
+    #[test]
+    fn reviewed_between_checks_the_local_review_day() {
+        let mut metadata = CardMetadata::default();
+        assert!(metadata.reviewed_between(None, None));
+        assert!(!metadata.reviewed_between(Some(date("2024-03-01")), None));
+
+        let reviewed = chrono::Local
+            .from_local_datetime(&date("2024-03-10").and_hms_opt(12, 0, 0).unwrap())
+            .unwrap();
+        metadata.last_reviewed = Some(reviewed.with_timezone(&chrono::Utc));
+        assert!(metadata.reviewed_between(None, None));
+        assert!(metadata.reviewed_between(Some(date("2024-03-10")), Some(date("2024-03-10"))));
+        assert!(metadata.reviewed_between(Some(date("2024-03-01")), None));
+        assert!(!metadata.reviewed_between(Some(date("2024-03-11")), None));
+        assert!(!metadata.reviewed_between(None, Some(date("2024-03-09"))));
+    }
+
+    #[test]
+    fn normalize_tags_collapses_case_padding_and_repeats() {
+        let tags = ["Spanish", " spanish ", "VERBS", "", "  "]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            normalize_tags(tags),
+            Ok(vec!["spanish".to_string(), "verbs".to_string()])
+        );
+        assert!(normalize_tags(vec!["a,b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn renumbered_compacts_ids_in_order_and_keeps_metadata() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3"), ("Q4", "A4")]);
+        deck.delete_card(1);
+        deck.delete_card(3);
+        review(&mut deck, 4, true);
+
+        let renumbered = deck.renumbered();
+        assert!(!deck.ids_are_sequential());
+        assert!(renumbered.ids_are_sequential());
+        assert_eq!(renumbered.next_id, 3);
+        assert_eq!(renumbered.cards[&1].question, "Q2");
+        assert_eq!(renumbered.cards[&2].question, "Q4");
+        assert_eq!(renumbered.cards[&2].id, 2);
+        assert_eq!(renumbered.cards[&2].metadata, deck.cards[&4].metadata);
+    }
+
+    #[test]
+    fn a_heavier_card_sorts_earlier_in_weighted_order() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3")]);
+        let today = chrono::Utc::now().date_naive();
+        assert_eq!(deck.weighted_order(today), vec![1, 2, 3]);
+
+        assert_eq!(deck.set_weight(3, 4), Some(4));
+        assert_eq!(deck.weighted_order(today), vec![3, 1, 2]);
+        assert_eq!(deck.set_weight(2, 0), Some(1));
+        assert_eq!(deck.set_weight(2, 50), Some(MAX_WEIGHT));
+        assert_eq!(deck.set_weight(9, 2), None);
+    }
+
+    #[test]
+    fn reset_all_stats_keeps_the_weight_and_fixed_interval() {
+        let mut deck = deck_with(&[("Q", "A")]);
+        deck.set_weight(1, 3);
+        deck.set_fixed_interval(1, Some(7));
+        deck.set_starred(1, true);
+        review(&mut deck, 1, true);
+
+        deck.reset_all_stats();
+        let card = &deck.cards[&1];
+        assert_eq!(card.metadata.times_reviewed, 0);
+        assert!(card.metadata.history.is_empty());
+        assert_eq!(card.metadata.weight, 3);
+        assert_eq!(card.metadata.fixed_interval_days, Some(7));
+        assert!(card.starred);
+    }
+
+    #[test]
+    fn search_in_can_match_case_sensitively() {
+        let deck = deck_with(&[
+            ("What does Vec::new return?", "An empty Vec"),
+            ("vec!", "A macro"),
+        ]);
+        let ids = |cards: Vec<&Flashcard>| cards.iter().map(|card| card.id).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(deck.search_in("Vec", SearchScope::All, false)),
+            vec![1, 2]
+        );
+        assert_eq!(ids(deck.search_in("Vec", SearchScope::All, true)), vec![1]);
+        assert_eq!(
+            ids(deck.search_in("vec", SearchScope::Questions, true)),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn a_json_export_loads_back_into_the_default_deck() {
+        let dir = temp_dir("export");
+        let path = dir.join("export.json");
+        let filename = path.to_str().unwrap();
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2")]);
+        review(&mut deck, 2, true);
+
+        assert_eq!(deck.export_json(filename).unwrap(), 2);
+        let exported: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(exported["schema"], SCHEMA_VERSION);
+
+        let collection = DeckCollection::load_from_file(filename).unwrap();
+        assert_eq!(collection.decks.len(), 1);
+        assert_eq!(collection.decks[DEFAULT_DECK].cards, deck.cards);
+        assert_eq!(collection.migrated_from, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_legacy_bare_deck_loads_into_the_default_deck() {
+        let deck = deck_with(&[("Q1", "A1")]);
+        let collection = DeckCollection::from_value(serde_json::to_value(&deck).unwrap()).unwrap();
+        assert_eq!(collection.decks[DEFAULT_DECK].cards, deck.cards);
+    }
+
+    #[test]
+    fn an_export_envelope_without_a_deck_is_rejected() {
+        let error = DeckCollection::from_value(serde_json::json!({ "schema": 2 })).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the export has a schema version but no deck"
+        );
+    }
+
+    fn v1_deck() -> serde_json::Value {
+        serde_json::json!({
+            "cards": {
+                "1": {
+                    "id": 1,
+                    "question": "Capital of France?",
+                    "answer": "Paris",
+                    "metadata": {
+                        "difficulty": "Easy",
+                        "times_reviewed": 2,
+                        "correct_count": 1,
+                        "last_reviewed": "2023-05-04"
+                    }
+                }
+            },
+            "next_id": 2
+        })
+    }
+
+    #[test]
+    fn a_v1_bare_deck_migrates_and_saves_as_the_current_version() {
+        let collection = DeckCollection::from_value(v1_deck()).unwrap();
+        assert_eq!(collection.migrated_from, Some(1));
+        assert_eq!(collection.schema_version, SCHEMA_VERSION);
+        let metadata = &collection.decks[DEFAULT_DECK].cards[&1].metadata;
+        assert_eq!(
+            metadata.last_reviewed.map(|date| date.date_naive()),
+            Some(date("2023-05-04"))
+        );
+        assert_eq!(metadata.ease_factor, default_ease_factor());
+
+        let dir = temp_dir("migrate");
+        let path = dir.join("flashcards.json");
+        let filename = path.to_str().unwrap();
+        collection.save_to_file(filename).unwrap();
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], SCHEMA_VERSION);
+        let reloaded = DeckCollection::load_from_file(filename).unwrap();
+        assert_eq!(reloaded.migrated_from, None);
+        assert_eq!(reloaded.decks[DEFAULT_DECK].cards[&1].metadata, *metadata);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_v1_collection_without_a_schema_version_migrates() {
+        let value = serde_json::json!({ "decks": { "french": v1_deck() } });
+        let collection = DeckCollection::from_value(value).unwrap();
+        assert_eq!(collection.migrated_from, Some(1));
+        let card = &collection.decks["french"].cards[&1];
+        assert_eq!(
+            card.metadata.last_reviewed.map(|date| date.date_naive()),
+            Some(date("2023-05-04"))
+        );
+    }
+
+    #[test]
+    fn a_newer_schema_version_is_rejected() {
+        let value = serde_json::json!({
+            "schema_version": SCHEMA_VERSION + 1,
+            "decks": {}
+        });
+        assert!(DeckCollection::from_value(value).is_err());
+        let value = serde_json::json!({ "schema": SCHEMA_VERSION + 1, "deck": v1_deck() });
+        assert!(DeckCollection::from_value(value).is_err());
+    }
+
+    #[test]
+    fn failing_cards_skips_unreviewed_cards() {
+        let mut deck = deck_with(&[("Q1", "A1"), ("Q2", "A2"), ("Q3", "A3"), ("Q4", "A4")]);
+        review(&mut deck, 1, false);
+        review(&mut deck, 2, true);
+        review(&mut deck, 2, false);
+        review(&mut deck, 3, true);
+
+        assert_eq!(deck.failing_cards(50.0), vec![1]);
+        assert_eq!(deck.failing_cards(60.0), vec![1, 2]);
+        assert!(FlashcardDeck::new().failing_cards(50.0).is_empty());
+    }
+
+    #[test]
+    fn record_quiz_score_keeps_only_qualifying_records() {
+        let mut deck = FlashcardDeck::new();
+        assert!(!deck.record_quiz_score(4, 4, date("2024-03-01")));
+        assert_eq!(deck.best_quiz_percentage, None);
+
+        assert!(deck.record_quiz_score(3, MIN_RECORD_QUIZ_CARDS, date("2024-03-02")));
+        assert!(!deck.record_quiz_score(3, MIN_RECORD_QUIZ_CARDS, date("2024-03-03")));
+        assert!(!deck.record_quiz_score(5, 10, date("2024-03-04")));
+        assert_eq!(deck.best_quiz_percentage, Some(60.0));
+        assert_eq!(deck.best_quiz_date, Some(date("2024-03-02")));
+
+        assert!(deck.record_quiz_score(9, 10, date("2024-03-05")));
+        assert_eq!(deck.best_quiz_percentage, Some(90.0));
+        assert_eq!(deck.best_quiz_date, Some(date("2024-03-05")));
+    }
+
+    #[test]
+    fn check_answer_accepts_only_an_exact_trimmed_match() {
+        assert!(check_answer("Paris", "  Paris\n", false));
+        assert!(!check_answer("Paris", "paris", false));
+        assert!(!check_answer("Paris", "Pariss", false));
+    }
+
+    #[test]
+    fn levenshtein_counts_single_character_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn check_answer_fuzzy_respects_the_threshold() {
+        assert!(check_answer_fuzzy("Shakespeare", "shakespere", 2, false));
+        assert!(check_answer_fuzzy(" Paris ", "PARIS", 2, false));
+        assert!(!check_answer_fuzzy("Jupiter", "Jupyetr", 2, false));
+        assert_eq!(levenshtein("jupiter", "jupyetr"), 3);
+    }
+
+    #[test]
+    fn exceeds_time_limit_only_past_the_limit() {
+        let limit = Some(Duration::from_secs(10));
+        assert!(!exceeds_time_limit(Duration::from_secs(3), limit));
+        assert!(!exceeds_time_limit(Duration::from_secs(10), limit));
+        assert!(exceeds_time_limit(Duration::from_millis(10_001), limit));
+        assert!(!exceeds_time_limit(Duration::from_secs(3600), None));
+    }
+
+    #[test]
+    fn clean_card_text_trims_and_rejects_blank_text() {
+        assert_eq!(
+            clean_card_text("question", "  Capital of France?\n"),
+            Ok("Capital of France?".to_string())
+        );
+        assert_eq!(clean_card_text("answer", "Paris"), Ok("Paris".to_string()));
+        assert!(clean_card_text("question", "").is_err());
+        assert_eq!(
+            clean_card_text("answer", " \t\n"),
+            Err("The answer can't be empty.".to_string())
+        );
+    }
+
+    #[test]
+    fn ignore_accents_accepts_answers_without_diacritics() {
+        assert!(check_answer("café", "cafe", true));
+        assert!(check_answer("naïve", " naive ", true));
+        assert!(!check_answer("café", "cafe", false));
+        assert!(check_answer_fuzzy("Crème brûlée", "creme brulee", 0, true));
+    }
+
+    #[test]
+    fn each_deck_file_has_its_own_quiz_session_file() {
+        assert_eq!(
+            QuizSession::path("decks/spanish.json"),
+            std::path::Path::new("decks/.spanish.json.quiz-session.json")
+        );
+        assert_ne!(
+            QuizSession::path("decks/spanish.json"),
+            QuizSession::path("decks/spanish.json5")
+        );
+    }
+
+    #[test]
+    fn heatmap_rows_end_with_the_week_of_today() {
+        let today = date("2024-03-13");
+        let counts = HashMap::from([(date("2024-03-04"), 2), (date("2024-03-13"), 5)]);
+
+        assert_eq!(heatmap_start(today, 2), date("2024-03-04"));
+        let rows = heatmap_rows(&counts, today, 2);
+        assert_eq!(rows[0], vec![Some(2), Some(0)]);
+        assert_eq!(rows[2], vec![Some(0), Some(5)]);
+        assert_eq!(rows[3], vec![Some(0), None]);
+        assert_eq!(heatmap_start(today, 1), date("2024-03-11"));
+        assert_eq!(heatmap_start(today, u32::MAX), chrono::NaiveDate::MIN);
+    }
+
+    #[test]
+    fn snapshots_belong_to_the_deck_file_with_the_same_name_and_extension() {
+        assert!(is_snapshot_of(
+            "deck-20240605-093000.json",
+            "decks/deck.json"
+        ));
+        assert!(is_snapshot_of(
+            "deck-20240605-093000.json5",
+            "decks/deck.json5"
+        ));
+        assert!(!is_snapshot_of(
+            "deck-20240605-093000.json5",
+            "decks/deck.json"
+        ));
+        assert!(!is_snapshot_of(
+            "deck-20240605-093000.json",
+            "decks/deck.json5"
+        ));
+        assert!(!is_snapshot_of(
+            "deck-old-20240605-093000.json",
+            "decks/deck.json"
+        ));
+    }
+}
@@ -1,10 +1,23 @@
-use chrono;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use colored::{ColoredString, Colorize};
+use flashcards_cli::{
+    CardKind, CardMetadata, CardReport, CardSelection, CsvField, DECK_FILE_NAME, DEFAULT_DECK,
+    DEFAULT_MASTERY_THRESHOLD, DEFAULT_MAX_ANSWER_CHARS, DeckCollection, Difficulty, Flashcard,
+    FlashcardDeck, LEITNER_INTERVAL_DAYS, LINT_CHECKS, MAX_INTERVAL_DAYS, QuizSession, Rating,
+    SAMPLE_TAG, SCHEMA_VERSION, SearchScope, SortKey, TagStats, card_sides, check_answer,
+    check_answer_fuzzy, clean_card_text, create_snapshot, exceeds_time_limit, format_last_reviewed,
+    format_review_date, heatmap_rows, heatmap_start, hint_for, list_snapshots, normalize_tag,
+    normalize_tags, parse_cloze, pick_distractors, prune_snapshots, rate_card, sort_cards, today,
+    unescape_separator,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use rand::Rng;
 use rand::seq::SliceRandom;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "flashcard")]
@@ -13,237 +26,1071 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    #[arg(short, long, default_value = "flashcards.json")]
+    /// The deck file to use (defaults to $FLASHCARDS_DIR or the platform config directory)
+    #[arg(short, long, default_value_t = default_deck_path())]
     file: String,
-}
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Add a new flashcard
-    Add {
-        /// The question for the flashcard
-        question: String,
-        /// The answer for the flashcard
-        answer: String,
-    },
-    /// Start a quiz session
-    Quiz,
-    /// List all flashcards
-    List,
-    /// View a specific flashcard by ID
-    View {
-        /// The ID of the flashcard to view
-        id: u32,
-    },
-    /// Delete a flashcard by ID
-    Delete {
-        /// The Id of the flashcard to delete
-        id: u32,
-    },
-    /// Reset all card stadistics
-    Reset,
-}
+    /// The named deck inside the file to use
+    #[arg(short, long, default_value = DEFAULT_DECK)]
+    deck: String,
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Flashcard {
-    pub id: u32,
-    pub question: String,
-    pub answer: String,
-    pub metadata: CardMetadata,
-}
+    /// Print machine-readable JSON instead of the human format
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Disable colored output (also disabled by NO_COLOR or when output isn't a terminal)
+    #[arg(long, global = true)]
+    no_color: bool,
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct CardMetadata {
-    pub difficulty: Difficulty,
-    pub times_reviewed: u32,
-    pub correct_count: u32,
-    pub last_reviewed: Option<String>, // We'll use simple string dates for now
+    /// Print terse, emoji-free output and exit with an error code on failure
+    #[arg(long, global = true)]
+    quiet: bool,
+
+    /// Run the command without writing anything to disk
+    #[arg(long, global = true)]
+    dry_run: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-pub enum Difficulty {
-    Easy,
-    Medium,
-    Hard,
+impl Cli {
+    fn use_color(&self) -> bool {
+        !self.no_color
+            && !self.json
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && io::stdout().is_terminal()
+    }
+
+    /// `text` as is, or with its leading emoji stripped under `--quiet`.
+    fn styled<'a>(&self, text: &'a str) -> &'a str {
+        if self.quiet {
+            text.trim_start_matches(|c: char| !c.is_ascii() || c == ' ')
+        } else {
+            text
+        }
+    }
+
+    /// Print an error to stderr and exit with a non-zero status.
+    fn fail(&self, message: &str) -> ! {
+        eprintln!("{}", self.styled(&format!("❌ {}", message)));
+        std::process::exit(1);
+    }
+
+    /// Report a failed command: fatal under `--quiet` so scripts can detect
+    /// it, otherwise just a message like the rest of the output.
+    fn report_error(&self, message: &str) {
+        if self.quiet {
+            self.fail(message);
+        }
+        println!("❌ {}", message);
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct FlashcardDeck {
-    pub cards: HashMap<u32, Flashcard>,
-    pub next_id: u32,
+/// Settings for a single quiz session.
+#[derive(clap::Args)]
+struct QuizArgs {
+    /// Quiz every card, including those not yet due for review
+    #[arg(long)]
+    all: bool,
+    /// Only quiz cards with this difficulty
+    #[arg(short, long, value_enum)]
+    difficulty: Option<Difficulty>,
+    /// Maximum number of cards to quiz
+    #[arg(short, long)]
+    limit: Option<usize>,
+    /// Type your answers instead of rating yourself
+    #[arg(short, long)]
+    typed: bool,
+    /// Accept typed answers within this many typos
+    #[arg(long, value_name = "N", requires = "typed")]
+    fuzzy: Option<usize>,
+    /// Accept typed answers that differ only in accents, so "cafe" matches "café"
+    #[arg(long, requires = "typed")]
+    ignore_accents: bool,
+    /// Show the answer as the prompt and recall the question
+    #[arg(short, long, conflicts_with = "both")]
+    reverse: bool,
+    /// Randomly pick the direction for each card
+    #[arg(short, long)]
+    both: bool,
+    /// Show each card's notes together with its answer
+    #[arg(long)]
+    show_notes: bool,
+    /// Pick the answer from this many multiple-choice options
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with = "typed",
+        value_parser = clap::value_parser!(u8).range(2..=26)
+    )]
+    choices: Option<u8>,
+    /// Rate each card with a 0-5 quality score instead of c/g/w
+    #[arg(long, conflicts_with_all = ["typed", "choices"])]
+    scale5: bool,
+    /// Use the Leitner box system instead of SM-2 scheduling
+    #[arg(long)]
+    leitner: bool,
+    /// Move difficulty one step per answer instead of setting it from the rating
+    #[arg(long)]
+    gradual: bool,
+    /// Bring cards answered wrong back a few cards later, until answered right
+    #[arg(long)]
+    retry: bool,
+    /// Only quiz starred cards
+    #[arg(long)]
+    starred: bool,
+    /// Also quiz archived cards
+    #[arg(long)]
+    include_archived: bool,
+    /// Also quiz mastered cards
+    #[arg(long)]
+    include_mastered: bool,
+    /// Correct answers in a row before a card counts as mastered
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MASTERY_THRESHOLD)]
+    mastery_threshold: u32,
+    /// Ring the terminal bell after each answer: once when right, twice when wrong
+    #[arg(long)]
+    sound: bool,
+    /// Ask weak and long-unreviewed cards first instead of shuffling
+    #[arg(long)]
+    smart: bool,
+    /// Only quiz reviewed cards with a success rate below the threshold
+    #[arg(long)]
+    failing: bool,
+    /// The success rate, in percent, below which --failing picks a card
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        default_value_t = 50.0,
+        requires = "failing"
+    )]
+    threshold: f64,
+    /// Ask the cards in ID order instead of shuffling
+    #[arg(long, conflicts_with_all = ["smart", "seed"])]
+    in_order: bool,
+    /// Ask the cards you haven't seen for the longest time first
+    #[arg(long, conflicts_with_all = ["smart", "in_order", "seed"])]
+    oldest: bool,
+    /// Continue the quiz you last quit before finishing
+    #[arg(long)]
+    resume: bool,
+    /// Shuffle the cards with this seed to get the same order every time
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+    /// Time each answer and keep track of average response times
+    #[arg(long)]
+    timed: bool,
+    /// Mark a card wrong when it takes longer than this many seconds
+    #[arg(long, value_name = "SECONDS", requires = "timed")]
+    time_limit: Option<u64>,
+    /// End the quiz after the card in progress once this many minutes have passed
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    minutes: Option<u64>,
 }
 
-impl FlashcardDeck {
-    pub fn new() -> Self {
-        FlashcardDeck {
-            cards: HashMap::new(),
-            next_id: 1,
+impl QuizArgs {
+    fn direction(&self) -> QuizDirection {
+        if self.both {
+            QuizDirection::Both
+        } else if self.reverse {
+            QuizDirection::Reverse
+        } else {
+            QuizDirection::Forward
         }
     }
 
-    pub fn add_card(&mut self, question: String, answer: String) -> u32 {
-        let card = Flashcard {
-            id: self.next_id,
-            question,
-            answer,
-            metadata: CardMetadata::default(),
-        };
+    fn selection(&self) -> CardSelection {
+        CardSelection {
+            starred_only: self.starred,
+            include_archived: self.include_archived,
+            include_mastered: self.include_mastered,
+        }
+    }
+
+    fn time_limit(&self) -> Option<Duration> {
+        self.time_limit.map(Duration::from_secs)
+    }
+
+    fn time_budget(&self) -> Option<Duration> {
+        self.minutes
+            .map(|minutes| Duration::from_secs(minutes * 60))
+    }
 
-        let id = card.id;
-        self.cards.insert(id, card);
-        self.next_id += 1;
-        id
+    /// Narrow the cards that passed the quiz's filters down to the due ones, put them in the
+    /// order they are asked, and keep at most `--limit` of them.
+    fn session_cards(
+        &self,
+        deck: &FlashcardDeck,
+        mut cards: Vec<u32>,
+        today: chrono::NaiveDate,
+    ) -> Vec<u32> {
+        let direction = self.direction();
+        cards.retain(|id| {
+            let metadata = &deck.cards[id].metadata;
+            self.all
+                || if self.leitner {
+                    metadata.is_leitner_due(today)
+                } else {
+                    match direction {
+                        QuizDirection::Forward => metadata.is_due(today),
+                        QuizDirection::Reverse => metadata.is_due_in(today, true),
+                        QuizDirection::Both => {
+                            metadata.is_due(today) || metadata.is_due_in(today, true)
+                        }
+                    }
+                }
+        });
+        if self.smart || self.oldest {
+            let order = if self.smart {
+                deck.weighted_order(today)
+            } else {
+                deck.by_last_reviewed()
+            };
+            let selected: HashSet<u32> = cards.into_iter().collect();
+            cards = order
+                .into_iter()
+                .filter(|id| selected.contains(id))
+                .collect();
+        }
+        if self.leitner {
+            // Lower boxes come first; the sort is stable so each box stays shuffled
+            cards.sort_by_key(|id| deck.cards[id].metadata.box_number);
+        }
+        if let Some(limit) = self.limit {
+            cards.truncate(limit);
+        }
+        cards
     }
+}
+
+/// Limit a command to cards last reviewed within a range of days.
+#[derive(clap::Args)]
+struct ReviewedRange {
+    /// Only include cards last reviewed on or after this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    since: Option<chrono::NaiveDate>,
+    /// Only include cards last reviewed on or before this date (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    until: Option<chrono::NaiveDate>,
+}
 
-    pub fn update_card_difficulty(&mut self, card_id: u32, difficulty: Difficulty, correct: bool) {
-        if let Some(card) = self.cards.get_mut(&card_id) {
-            card.metadata.difficulty = difficulty;
-            card.metadata.times_reviewed += 1;
-            if correct {
-                card.metadata.correct_count += 1;
+impl ReviewedRange {
+    fn check(&self) -> Result<(), String> {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) if since > until => {
+                Err(format!("--since {} is after --until {}.", since, until))
             }
-            card.metadata.last_reviewed = Some(chrono::Utc::now().format("%Y-%m-%d").to_string());
+            _ => Ok(()),
         }
     }
 
-    pub fn delete_card(&mut self, card_id: u32) -> bool {
-        self.cards.remove(&card_id).is_some()
+    fn includes(&self, card: &Flashcard) -> bool {
+        card.metadata.reviewed_between(self.since, self.until)
+    }
+}
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// $FLASHCARDS_DIR, or the platform config directory when it isn't set.
+fn flashcards_dir() -> Option<std::path::PathBuf> {
+    match std::env::var_os("FLASHCARDS_DIR") {
+        Some(dir) => Some(std::path::PathBuf::from(dir)),
+        None => directories::ProjectDirs::from("", "", "flashcards")
+            .map(|dirs| dirs.config_dir().to_path_buf()),
     }
+}
 
-    pub fn get_card(&self, card_id: u32) -> Option<&Flashcard> {
-        self.cards.get(&card_id)
+/// Resolve where the deck file lives when `--file` isn't given.
+fn default_deck_path() -> String {
+    match flashcards_dir() {
+        Some(dir) => dir.join(DECK_FILE_NAME).to_string_lossy().into_owned(),
+        None => DECK_FILE_NAME.to_string(),
     }
+}
+
+/// Settings read from `config.toml` next to the default deck file.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    keys: KeyBindings,
+    /// Answers longer than this make `add` warn, or refuse the card with `--strict`
+    max_answer_chars: Option<usize>,
+}
 
-    pub fn reset_all_stats(&mut self) {
-        for card in self.cards.values_mut() {
-            card.metadata = CardMetadata::default();
+impl Config {
+    /// Load the config file, falling back to the defaults when there is none.
+    fn load() -> Result<Self, String> {
+        let Some(path) = flashcards_dir().map(|dir| dir.join(CONFIG_FILE_NAME)) else {
+            return Ok(Config::default());
+        };
+        if !path.exists() {
+            return Ok(Config::default());
         }
+        let invalid =
+            |message: String| format!("Invalid config file {}: {}", path.display(), message);
+        let content = fs::read_to_string(&path).map_err(|error| invalid(error.to_string()))?;
+        let config: Config =
+            toml::from_str(&content).map_err(|error| invalid(error.message().to_string()))?;
+        config.keys.validate().map_err(invalid)?;
+        Ok(config)
     }
+}
+
+/// The keys used to rate a card in a self-rated quiz.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct KeyBindings {
+    easy: char,
+    medium: char,
+    hard: char,
+    quit: char,
+    repeat: char,
+}
 
-    pub fn get_random_cards_ids(&self) -> Vec<u32> {
-        let mut cards_ids: Vec<u32> = self.cards.keys().copied().collect();
-        let mut rng = rand::rng();
-        cards_ids.shuffle(&mut rng);
-        cards_ids
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            easy: 'c',
+            medium: 'g',
+            hard: 'w',
+            quit: 'q',
+            repeat: 'r',
+        }
     }
+}
 
-    pub fn save_to_file(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let json_data = serde_json::to_string_pretty(self)?;
-        fs::write(filename, json_data)?;
-        Ok(())
+impl KeyBindings {
+    fn keys(&self) -> [char; 5] {
+        [self.easy, self.medium, self.hard, self.quit, self.repeat]
     }
 
-    pub fn load_from_file(filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let file_content = fs::read_to_string(filename)?;
-        let deck: FlashcardDeck = serde_json::from_str(&file_content)?;
-        Ok(deck)
+    /// Whether `input` is exactly `key`, ignoring case and surrounding whitespace.
+    fn pressed(key: char, input: &str) -> bool {
+        let mut chars = input.trim().chars();
+        match (chars.next(), chars.next()) {
+            (Some(typed), None) => typed.to_lowercase().eq(key.to_lowercase()),
+            _ => false,
+        }
     }
-}
 
-impl Default for CardMetadata {
-    fn default() -> Self {
-        CardMetadata {
-            difficulty: Difficulty::Medium,
-            times_reviewed: 0,
-            correct_count: 0,
-            last_reviewed: None,
+    /// Keys must be distinct, visible characters; case is ignored when rating.
+    fn validate(&self) -> Result<(), String> {
+        let keys = self
+            .keys()
+            .map(|key| key.to_lowercase().next().unwrap_or(key));
+        if keys
+            .iter()
+            .any(|key| key.is_whitespace() || key.is_control())
+        {
+            return Err("keys must be visible characters".to_string());
         }
+        if keys.iter().collect::<HashSet<_>>().len() < keys.len() {
+            return Err("each rating needs its own key".to_string());
+        }
+        Ok(())
     }
 }
 
+#[derive(Subcommand)]
+enum Commands {
+    /// Add a new flashcard, prompting for anything not given on the command line
+    Add {
+        /// The question for the flashcard
+        question: Option<String>,
+        /// The answer for the flashcard
+        answer: Option<String>,
+        /// Add a fill-in-the-blank card, marking each blank like {{this}}
+        #[arg(long, value_name = "TEXT", conflicts_with_all = ["question", "answer"])]
+        cloze: Option<String>,
+        /// Comma-separated tags for the flashcard
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// Add the card even if another card has the same question
+        #[arg(long)]
+        allow_duplicates: bool,
+        /// Notes such as mnemonics or sources for the flashcard
+        #[arg(short, long)]
+        notes: Option<String>,
+        /// An image or other file to show with the flashcard
+        #[arg(long, value_name = "PATH")]
+        media: Option<String>,
+        /// Warn about answers longer than this many characters (defaults to the config, or 500)
+        #[arg(long, value_name = "N")]
+        max_answer_chars: Option<usize>,
+        /// Refuse the card instead of warning when the answer is too long
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Start a quiz session
+    Quiz(QuizArgs),
+    /// List all flashcards
+    List {
+        /// Only list flashcards with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// The order to list flashcards in
+        #[arg(short, long, value_enum, default_value_t = SortKey::Id)]
+        sort: SortKey,
+        /// Reverse the sort order
+        #[arg(short, long)]
+        reverse: bool,
+        /// The page of results to show
+        #[arg(short, long)]
+        page: Option<usize>,
+        /// Number of flashcards per page
+        #[arg(long, value_name = "N")]
+        per_page: Option<usize>,
+        /// Only list starred flashcards
+        #[arg(long)]
+        starred: bool,
+        /// Also list archived flashcards
+        #[arg(long)]
+        include_archived: bool,
+        /// Only print the number of matching flashcards
+        #[arg(long)]
+        count: bool,
+        /// Show the flashcards as a table
+        #[arg(long)]
+        table: bool,
+        /// Only list the N matching flashcards with the lowest IDs
+        #[arg(long, value_name = "N", conflicts_with = "last")]
+        first: Option<usize>,
+        /// Only list the N matching flashcards with the highest IDs, such as the newest ones
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+        #[command(flatten)]
+        reviewed: ReviewedRange,
+        /// Write the list to this file instead of the terminal
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+    },
+    /// View a specific flashcard by ID
+    View {
+        /// The ID of the flashcard to view
+        id: u32,
+        /// Open the flashcard's media file in the default viewer
+        #[arg(long)]
+        open_media: bool,
+        /// Print only the question, without any decoration
+        #[arg(long, conflicts_with = "answer_only")]
+        question_only: bool,
+        /// Print only the answer, without any decoration
+        #[arg(long)]
+        answer_only: bool,
+    },
+    /// Review a single card: the most overdue one, or a random one
+    Peek {
+        /// Pick a random card instead of the most overdue one
+        #[arg(long)]
+        random: bool,
+    },
+    /// Show random flashcards to test yourself, without recording anything
+    Random {
+        /// How many flashcards to show in a row
+        #[arg(short, long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Show every recorded review of a flashcard
+    History {
+        /// The ID of the flashcard
+        id: u32,
+    },
+    /// Delete a flashcard by ID
+    Delete {
+        /// The Id of the flashcard to delete
+        id: u32,
+        /// Delete without asking for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete every flashcard with a given tag and/or difficulty
+    DeleteMatching {
+        /// Only delete flashcards with this tag
+        #[arg(short, long)]
+        tag: Option<String>,
+        /// Only delete flashcards with this difficulty
+        #[arg(short, long, value_enum)]
+        difficulty: Option<Difficulty>,
+        /// Delete without asking for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+    /// Fill an empty deck with a few sample flashcards to try things out
+    Seed {
+        /// Add the samples even if the deck already has flashcards
+        #[arg(long)]
+        force: bool,
+    },
+    /// Reset all card stadistics
+    Reset,
+    /// Give the flashcards new IDs from 1 without gaps, keeping their order
+    Renumber {
+        /// Renumber without asking for confirmation
+        #[arg(long)]
+        force: bool,
+    },
+    /// Edit the question and/or answer of a flashcard
+    Edit {
+        /// The ID of the flashcard to edit
+        id: u32,
+        /// The new question for the flashcard
+        #[arg(short, long)]
+        question: Option<String>,
+        /// The new answer for the flashcard
+        #[arg(short, long)]
+        answer: Option<String>,
+        /// The new notes for the flashcard (an empty string removes them)
+        #[arg(short, long)]
+        notes: Option<String>,
+    },
+    /// Add or remove tags on a flashcard
+    Tag {
+        /// The ID of the flashcard to tag
+        id: u32,
+        /// Comma-separated tags to add
+        #[arg(short, long, value_delimiter = ',')]
+        add: Vec<String>,
+        /// Comma-separated tags to remove
+        #[arg(short, long, value_delimiter = ',')]
+        remove: Vec<String>,
+    },
+    /// Rename a tag on every flashcard that has it
+    RenameTag {
+        /// The tag to rename
+        from: String,
+        /// The new name, merged into the card's tags if it already has it
+        to: String,
+    },
+    /// Star a flashcard to mark it as high priority
+    Star {
+        /// The ID of the flashcard to star
+        id: u32,
+    },
+    /// Remove the star from a flashcard
+    Unstar {
+        /// The ID of the flashcard to unstar
+        id: u32,
+    },
+    /// Archive a flashcard, hiding it without losing its statistics
+    Archive {
+        /// The ID of the flashcard to archive
+        id: u32,
+    },
+    /// Bring an archived flashcard back
+    Unarchive {
+        /// The ID of the flashcard to unarchive
+        id: u32,
+    },
+    /// List archived flashcards
+    Archived,
+    /// List mastered flashcards, which quizzes skip
+    Mastered,
+    /// Put a mastered flashcard back into rotation
+    Unmaster {
+        /// The ID of the flashcard
+        id: u32,
+    },
+    /// Export all flashcards to another format
+    ///
+    /// To move your cards into Anki, run `export --format anki --output deck.txt`, then in
+    /// Anki choose File > Import, pick deck.txt, and check that the three fields map to Front,
+    /// Back and Tags before importing.
+    Export {
+        /// The export format (csv, md, anki, json, stats-csv)
+        #[arg(long, default_value = "csv")]
+        format: String,
+        /// The file to write the exported cards to
+        #[arg(short, long)]
+        output: String,
+        /// Comma-separated columns of a csv export, in order (defaults to all of them)
+        #[arg(long, value_delimiter = ',')]
+        fields: Vec<CsvField>,
+    },
+    /// Search flashcards by question and answer text
+    Search {
+        /// The text to look for (case-insensitive unless --case-sensitive is passed)
+        query: String,
+        /// Only search the questions
+        #[arg(long, conflicts_with = "answers_only")]
+        questions_only: bool,
+        /// Only search the answers
+        #[arg(long)]
+        answers_only: bool,
+        /// Match upper and lower case exactly, such as for code
+        #[arg(long)]
+        case_sensitive: bool,
+    },
+    /// Merge cards that share the same question
+    Dedup,
+    /// Report deck-quality problems such as empty fields and duplicates
+    Lint,
+    /// List the cards that are due for review
+    Due {
+        /// Only print the number of due cards
+        #[arg(long)]
+        count_only: bool,
+    },
+    /// Set how many cards you want to review each day (0 turns the goal off)
+    Goal {
+        /// Cards to review per day
+        count: u32,
+    },
+    /// Show a flashcard every few days, whatever its results (0 days goes back to normal)
+    Schedule {
+        /// The ID of the flashcard to schedule
+        id: u32,
+        /// Days between reviews, at most 36500 (about a hundred years)
+        #[arg(value_parser = clap::value_parser!(u32).range(..=i64::from(MAX_INTERVAL_DAYS)))]
+        days: u32,
+    },
+    /// Make `quiz --smart` pick a flashcard more often (1 is normal, 10 the most)
+    Weight {
+        /// The ID of the flashcard to weight
+        id: u32,
+        /// The weight, from 1 to 10
+        value: u8,
+    },
+    /// Show the current and longest daily study streak
+    Streak,
+    /// Show a calendar of how many reviews you did each day
+    Heatmap {
+        /// Number of weeks to show, ending with the current one (1 to 520)
+        #[arg(
+            short,
+            long,
+            default_value_t = 12,
+            value_parser = clap::value_parser!(u32).range(1..=MAX_HEATMAP_WEEKS)
+        )]
+        weeks: u32,
+    },
+    /// Show how many cards are in each Leitner box
+    Boxes,
+    /// Show statistics for the whole deck
+    Stats {
+        /// Include archived flashcards in the statistics
+        #[arg(long)]
+        include_archived: bool,
+        /// Also show question and answer lengths
+        #[arg(short, long)]
+        verbose: bool,
+        /// Also break the statistics down by tag, weakest first
+        #[arg(long)]
+        by_tag: bool,
+        #[command(flatten)]
+        reviewed: ReviewedRange,
+        /// Write the statistics to this file instead of the terminal
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+    },
+    /// Restore the file to how it was before the last change
+    Undo,
+    /// Save a timestamped snapshot of the deck file in the backups folder
+    Backup {
+        /// Keep only this many snapshots, deleting the oldest
+        #[arg(long, value_name = "N")]
+        max_backups: Option<usize>,
+    },
+    /// List the backup snapshots, or restore the deck file from one
+    Restore {
+        /// The snapshot to restore (as shown by 'restore' without a name)
+        name: Option<String>,
+    },
+    /// List all decks in the file
+    Decks,
+    /// Move a flashcard into another deck, keeping its statistics
+    Move {
+        /// The ID of the flashcard to move
+        id: u32,
+        /// The deck to move it to (created if it doesn't exist)
+        to: String,
+    },
+    /// Copy a flashcard into another deck
+    Copy {
+        /// The ID of the flashcard to copy
+        id: u32,
+        /// The deck to copy it to (created if it doesn't exist)
+        to: String,
+        /// Keep the card's statistics instead of starting the copy fresh
+        #[arg(long)]
+        keep_stats: bool,
+    },
+    /// Add the cards of another deck file to this deck
+    Merge {
+        /// The deck file to merge cards from
+        other: String,
+        /// The deck inside the other file to merge (defaults to the name of this one)
+        #[arg(long, value_name = "DECK")]
+        from_deck: Option<String>,
+        /// Fold the statistics of cards with a question already in this deck into that card
+        #[arg(long)]
+        merge_stats: bool,
+    },
+    /// Import flashcards from another format
+    Import {
+        /// The import format (csv, txt, quizlet)
+        #[arg(long, default_value = "csv")]
+        format: String,
+        /// The file to read cards from
+        #[arg(short, long)]
+        input: String,
+        /// The text between question and answer on each line of a txt import
+        #[arg(short, long, default_value = "|")]
+        separator: String,
+        /// The text between a term and its definition in a quizlet import (\t for a tab)
+        #[arg(long, default_value = "\\t")]
+        term_sep: String,
+        /// The text between cards in a quizlet import (\n for a newline)
+        #[arg(long, default_value = "\\n")]
+        card_sep: String,
+    },
+    /// Print where the deck file is and whether it exists yet
+    Where,
+    /// Print a shell completion script
+    #[command(hide = true)]
+    Completions {
+        /// The shell to generate completions for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    // Load existing deck or create new one
-    let mut deck = if std::path::Path::new(&cli.file).exists() {
-        FlashcardDeck::load_from_file(&cli.file)?
+    if let Commands::Completions { shell } = &cli.command {
+        clap_complete::generate(*shell, &mut Cli::command(), "flashcard", &mut io::stdout());
+        return Ok(());
+    }
+    colored::control::set_override(cli.use_color());
+
+    // Runs before loading so it also works when the deck file is damaged
+    if let Commands::Where = &cli.command {
+        let path = std::path::absolute(&cli.file)?;
+        let exists = path.exists();
+        if cli.json {
+            let location = serde_json::json!({ "path": path, "exists": exists });
+            println!("{}", serde_json::to_string_pretty(&location)?);
+        } else if cli.quiet {
+            println!("{}", path.display());
+        } else if exists {
+            println!("📁 Deck file: {}", path.display());
+        } else {
+            println!(
+                "📁 Deck file: {} (not created yet; it is written when you add a card)",
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    // Load existing decks and select the requested one, creating it if needed
+    let mut collection = if std::path::Path::new(&cli.file).exists() {
+        load_or_recover(&cli)?
     } else {
-        FlashcardDeck::new()
+        DeckCollection::new()
     };
+    let mut deck = collection.decks.get(&cli.deck).cloned().unwrap_or_default();
 
     match &cli.command {
-        Commands::Add { question, answer } => {
-            let id = deck.add_card(question.clone(), answer.clone());
-            deck.save_to_file(&cli.file)?;
-            println!("Added flashcard #{}: {}", id, question);
+        Commands::Add {
+            question,
+            answer,
+            cloze,
+            tags,
+            allow_duplicates,
+            notes,
+            media,
+            max_answer_chars,
+            strict,
+        } => {
+            let interactive = cloze.is_none() && question.is_none();
+            let question = match cloze.as_ref().or(question.as_ref()) {
+                Some(question) => question.clone(),
+                None => read_multiline(cli.styled("❓ Question"))?,
+            };
+            let question =
+                clean_card_text("question", &question).unwrap_or_else(|message| cli.fail(&message));
+            let answer = match (cloze, answer) {
+                (Some(_), _) => String::new(),
+                (None, Some(answer)) => answer.clone(),
+                (None, None) => read_multiline(cli.styled("💡 Answer"))?,
+            };
+            let answer = match cloze {
+                Some(_) => answer,
+                None => {
+                    clean_card_text("answer", &answer).unwrap_or_else(|message| cli.fail(&message))
+                }
+            };
+            let mut tags = tags.clone();
+            if interactive {
+                let mut input = String::new();
+                print!(
+                    "{}",
+                    cli.styled("🏷️  Tags (comma-separated, Enter for none): ")
+                );
+                read_line(&mut input)?;
+                tags.extend(input.split(',').map(String::from));
+            }
+            let tags = normalize_tags(tags).unwrap_or_else(|message| cli.fail(&message));
+            let max_answer_chars = max_answer_chars.unwrap_or_else(|| {
+                Config::load()
+                    .unwrap_or_else(|message| cli.fail(&message))
+                    .max_answer_chars
+                    .unwrap_or(DEFAULT_MAX_ANSWER_CHARS)
+            });
+            let answer_chars = answer.chars().count();
+            let too_long = answer_chars > max_answer_chars;
+            if let Some(Err(message)) = cloze.as_ref().map(|_| parse_cloze(&question)) {
+                cli.report_error(&format!("Invalid cloze text: {}", message));
+            } else if too_long && *strict {
+                cli.report_error(&format!(
+                    "The answer is {} characters, over the limit of {}. Split it into smaller cards.",
+                    answer_chars, max_answer_chars
+                ));
+            } else if !*allow_duplicates
+                && let Some(existing) = deck.find_duplicate(&question)
+                && !confirm(cli.styled(&format!(
+                    "⚠️  Flashcard #{} already has this question. Add anyway? (y/N): ",
+                    existing
+                )))?
+            {
+                println!("{}", cli.styled("❌ Add cancelled."));
+            } else {
+                let id = match cloze {
+                    Some(_) => deck.add_cloze_card(question.clone())?,
+                    None => deck.add_card(question.clone(), answer),
+                };
+                deck.update_card_tags(id, tags, Vec::new());
+                if let Some(notes) = notes {
+                    deck.update_card_notes(id, notes.clone());
+                }
+                if let Some(media) = media {
+                    // Stored absolute so the card works from any directory
+                    let media = std::path::absolute(media)?;
+                    deck.update_card_media(id, media.to_string_lossy().into_owned());
+                }
+                save_deck(&mut collection, &cli, &deck)?;
+                if cli.quiet {
+                    println!("{}", id);
+                } else {
+                    println!("Added flashcard #{}: {}", id, question);
+                }
+                if deck.cards[&id].answer_repeats_question() {
+                    eprintln!(
+                        "{}",
+                        cli.styled("⚠️  The answer is the same as the question. Fix it with 'flashcard edit'.")
+                    );
+                }
+                if too_long {
+                    eprintln!(
+                        "{}",
+                        cli.styled(&format!(
+                            "⚠️  The answer is {} characters long. Short answers are easier to remember; consider splitting the card.",
+                            answer_chars
+                        ))
+                    );
+                }
+            }
         }
-        Commands::List => {
-            if deck.cards.is_empty() {
-                println!("No flashcards found. Add some with 'flashcard add <question> <answer>'");
+        Commands::List {
+            tag,
+            sort,
+            reverse,
+            page,
+            per_page,
+            starred,
+            include_archived,
+            count,
+            table,
+            first,
+            last,
+            reviewed,
+            output,
+        } => {
+            reviewed
+                .check()
+                .unwrap_or_else(|message| cli.fail(&message));
+            let tag = tag.as_deref().map(normalize_tag);
+            let mut cards: Vec<&Flashcard> = deck
+                .cards
+                .values()
+                .filter(|card| tag.as_ref().is_none_or(|tag| card.has_tag(tag)))
+                .filter(|card| !*starred || card.starred)
+                .filter(|card| *include_archived || !card.archived)
+                .filter(|card| reviewed.includes(card))
+                .collect();
+            if first.is_some() || last.is_some() {
+                cards.sort_by_key(|card| card.id);
+                if let Some(first) = first {
+                    cards.truncate(*first);
+                }
+                if let Some(last) = last {
+                    cards.drain(..cards.len().saturating_sub(*last));
+                }
+            }
+            sort_cards(&mut cards, *sort, *reverse);
+
+            let paginated = page.is_some() || per_page.is_some();
+            let per_page = per_page
+                .unwrap_or(if paginated { 20 } else { cards.len() })
+                .max(1);
+            let total_pages = cards.len().div_ceil(per_page).max(1);
+            let page = page.unwrap_or(1);
+            let page_cards = cards
+                .chunks(per_page)
+                .nth(page.saturating_sub(1))
+                .unwrap_or_default();
+
+            if !*count && (page == 0 || page > total_pages) {
+                cli.fail(&format!("Page {} does not exist (1-{}).", page, total_pages));
+            }
+
+            let mut out = OutputBuffer::new(output);
+            if *count {
+                writeln!(out, "{}", cards.len())?;
+            } else if cli.json {
+                print_cards_json(&mut out, page_cards)?;
+            } else if deck.cards.is_empty() {
+                writeln!(out, "No flashcards found. Add some with 'flashcard add <question> <answer>'")?;
+            } else if cards.is_empty() {
+                writeln!(out, "No flashcards match those filters.")?;
             } else {
-                println!("Flashcards in deck ({}):", deck.cards.len());
+                writeln!(
+                    out,
+                    "{}",
+                    format!("Flashcards in deck ({}):", cards.len()).bold()
+                )?;
 
-                let mut cards: Vec<&Flashcard> = deck.cards.values().collect();
-                cards.sort_by_key(|card| card.id);
                 // for card in deck.cards.values() {
                 //     println!("#{}: {} -> {}", card.id, card.question, card.answer);
                 // }
-                for card in cards {
-                    let success_rate = if card.metadata.times_reviewed > 0 {
-                        (card.metadata.correct_count as f64 / card.metadata.times_reviewed as f64)
-                            * 100.0
-                    } else {
-                        0.0
-                    };
-
-                    let difficulty_emoji = match card.metadata.difficulty {
-                        Difficulty::Easy => "🟢",
-                        Difficulty::Medium => "🟡",
-                        Difficulty::Hard => "🔴",
-                    };
-
-                    println!(
-                        "#{} {} [{}] Success: {:.0}% ({}/{})",
-                        card.id,
-                        difficulty_emoji,
-                        format!("{:.30}", card.question).trim(),
-                        success_rate,
-                        card.metadata.correct_count,
-                        card.metadata.times_reviewed
-                    );
-
-                    if card.metadata.times_reviewed > 0 {
-                        println!(
-                            "    Last reviewed: {}",
-                            card.metadata
-                                .last_reviewed
-                                .as_ref()
-                                .unwrap_or(&"Never".to_string())
-                        );
+                if *table {
+                    writeln!(out, "{}", cards_table(page_cards))?;
+                    writeln!(out)?;
+                } else {
+                    for card in page_cards {
+                        print_card_summary(&mut out, card)?;
                     }
-                    println!();
+                }
+                if paginated {
+                    writeln!(out, "Page {}/{}", page, total_pages)?;
+                    writeln!(out)?;
                 }
 
                 // Print deck stadistics
-                let total_reviews: u32 =
-                    deck.cards.values().map(|c| c.metadata.times_reviewed).sum();
-                let total_correct: u32 =
-                    deck.cards.values().map(|c| c.metadata.correct_count).sum();
+                let total_reviews: u32 = cards.iter().map(|c| c.metadata.times_reviewed).sum();
+                let total_correct: u32 = cards.iter().map(|c| c.metadata.correct_count).sum();
                 let overall_success = if total_reviews > 0 {
                     (total_correct as f64 / total_reviews as f64) * 100.0
                 } else {
                     0.0
                 };
 
-                println!("📈 Deck Statistics:");
-                println!("   Total cards: {}", deck.cards.len());
-                println!("   Total reviews: {}", total_reviews);
-                println!("   Overall success rate: {:.1}%", overall_success);
+                writeln!(out, "{}", "📈 Deck Statistics:".bold())?;
+                writeln!(out, "   Total cards: {}", cards.len())?;
+                writeln!(out, "   Total reviews: {}", total_reviews)?;
+                writeln!(
+                    out,
+                    "   Overall success rate: {}",
+                    success_rate_text(overall_success, 1)
+                )?;
             }
+            write_output(&cli, output, &out.text)?;
         }
-        Commands::Quiz => {
+        Commands::Quiz(args) => {
             if deck.cards.is_empty() {
                 println!("No flashcards to quiz! Add some first.");
+            } else if args.resume {
+                match QuizSession::load(&QuizSession::path(&cli.file))? {
+                    None => println!("❌ No unfinished quiz to resume."),
+                    Some(session) if session.deck != cli.deck => println!(
+                        "❌ The unfinished quiz is for deck '{}'. Add --deck {} to resume it.",
+                        session.deck, session.deck
+                    ),
+                    Some(mut session) => {
+                        // Cards deleted since the quiz was saved are skipped
+                        let before = session.remaining.len();
+                        session.remaining.retain(|id| deck.cards.contains_key(id));
+                        session.session_len -= before - session.remaining.len();
+                        run_quiz_session(&mut deck, session, args, &cli)?;
+                        save_deck(&mut collection, &cli, &deck)?;
+                    }
+                }
             } else {
-                run_quiz(&mut deck)?;
-                deck.save_to_file(&cli.file)?;
+                let mut cards = if args.in_order {
+                    deck.get_cards_ids_in_order(&args.selection())
+                } else {
+                    deck.get_random_cards_ids(&args.selection(), args.seed)
+                };
+                let has_quizzable_cards = !cards.is_empty();
+                if let Some(difficulty) = &args.difficulty {
+                    let matching = deck.cards_by_difficulty(difficulty);
+                    cards.retain(|id| matching.contains(id));
+                }
+                if args.failing {
+                    let failing = deck.failing_cards(args.threshold);
+                    cards.retain(|id| failing.contains(id));
+                }
+
+                let has_matching_cards = !cards.is_empty();
+                let cards = args.session_cards(&deck, cards, today());
+
+                if !has_quizzable_cards && args.starred {
+                    println!("No starred cards to quiz! Star some with the star command.");
+                } else if !has_quizzable_cards {
+                    println!(
+                        "Every flashcard is archived or mastered! Use --include-archived or --include-mastered to quiz them."
+                    );
+                } else if !has_matching_cards && args.failing {
+                    println!(
+                        "💪 No reviewed cards are below {}% success. Nothing to focus on!",
+                        args.threshold
+                    );
+                } else if !has_matching_cards {
+                    println!("No cards with that difficulty to quiz! Try another one.");
+                } else if cards.is_empty() {
+                    println!("🎉 No cards are due for review! Use --all to quiz every card.");
+                } else {
+                    run_quiz_session(&mut deck, QuizSession::new(&cli.deck, cards), args, &cli)?;
+                    save_deck(&mut collection, &cli, &deck)?;
+                }
             }
         }
-        Commands::View { id } => match deck.get_card(*id) {
+        Commands::View {
+            id,
+            open_media,
+            question_only,
+            answer_only,
+        } => match deck.get_card(*id) {
+            Some(card) if *question_only || *answer_only => {
+                let (question, answer) = card_sides(card, false);
+                println!("{}", if *question_only { question } else { answer });
+            }
+            Some(card) if cli.json => {
+                println!("{}", serde_json::to_string_pretty(&CardReport::new(card))?);
+            }
             Some(card) => {
-                println!("📄 Flashcard #{}:", card.id);
+                println!(
+                    "{}{}",
+                    format!("📄 Flashcard #{}:", card.id).bold(),
+                    if card.starred { " ⭐" } else { "" }
+                );
                 println!("❓ Question: {}", card.question);
                 println!("💡 Answer: {}", card.answer);
+                if let Some(notes) = &card.notes {
+                    println!("📝 Notes: {}", notes);
+                }
+                if !card.tags.is_empty() {
+                    println!("🏷️  Tags: {}", card.tags.join(", "));
+                }
+                if let Some(media) = &card.media {
+                    println!("📎 Media: {}", media);
+                    if !std::path::Path::new(media).exists() {
+                        println!("⚠️  The media file doesn't exist.");
+                    } else if *open_media && let Err(error) = open_in_viewer(media) {
+                        println!("❌ Could not open {}: {}", media, error);
+                    }
+                } else if *open_media {
+                    println!("❌ Flashcard #{} has no media.", id);
+                }
                 println!();
 
                 let difficulty_emoji = match card.metadata.difficulty {
@@ -251,132 +1098,1906 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     Difficulty::Medium => "🟡 Medium",
                     Difficulty::Hard => "🔴 Hard",
                 };
-                println!("📊 Statistics:");
+                println!("{}", "📊 Statistics:".bold());
                 println!("   Difficulty: {}", difficulty_emoji);
                 println!("   Times reviewed: {}", card.metadata.times_reviewed);
                 println!("   Correct answers: {}", card.metadata.correct_count);
+                let by_difficulty: Vec<String> = card
+                    .metadata
+                    .results_by_difficulty()
+                    .iter()
+                    .map(|(difficulty, correct, total)| {
+                        format!("{:?}: {}/{}", difficulty, correct, total)
+                    })
+                    .collect();
+                if !by_difficulty.is_empty() {
+                    println!("   Correct by difficulty: {}", by_difficulty.join(", "));
+                }
 
                 if card.metadata.times_reviewed > 0 {
-                    let success_rate = (card.metadata.correct_count as f64
-                        / card.metadata.times_reviewed as f64)
-                        * 100.0;
-                    println!("   Success rate: {:.1}%", success_rate);
+                    println!(
+                        "   Success rate: {}",
+                        success_rate_text(card.metadata.success_rate(), 1)
+                    );
+                    if card.metadata.reverse.times_reviewed > 0 {
+                        for (label, reversed) in
+                            [("Question → answer", false), ("Answer → question", true)]
+                        {
+                            println!(
+                                "     {}: {}",
+                                label,
+                                direction_results_text(&card.metadata, reversed)
+                            );
+                        }
+                    }
                     println!(
                         "   Last reviewed: {}",
                         card.metadata
                             .last_reviewed
                             .as_ref()
-                            .unwrap_or(&"Never".to_string())
+                            .map_or("Never".to_string(), format_last_reviewed)
                     );
                 } else {
                     println!("   Success rate: Not yet reviewed");
                 }
+                if let Some(due_date) = card.metadata.next_due_date() {
+                    println!("   Next review: {}", due_date);
+                }
+                if card.metadata.fixed_interval_days.is_none()
+                    && let Some(due_date) = card.metadata.reverse.due_date
+                {
+                    println!("   Next reverse review: {}", due_date);
+                }
+                if let Some(days) = card.metadata.fixed_interval_days {
+                    println!("   Fixed interval: every {}", days_label(days));
+                }
+                if card.metadata.weight > 1 {
+                    println!("   Weight: {}", card.metadata.weight);
+                }
+                if let Some(seconds) = card.metadata.avg_response_secs {
+                    println!("   Average response time: {:.1}s", seconds);
+                }
             }
             None => {
                 println!("❌ Flashcard #{} not found.", id);
             }
         },
-        Commands::Delete { id } => {
-            if deck.delete_card(*id) {
-                deck.save_to_file(&cli.file)?;
-                println!("🗑️  Deleted flashcard #{}", id);
+        Commands::Random { count } => {
+            let card_ids = deck.get_random_cards_ids(&CardSelection::default(), None);
+            if card_ids.is_empty() {
+                println!("No flashcards to show! Add some first.");
+            }
+            let shown = card_ids.len().min(*count);
+            for (index, card_id) in card_ids.iter().take(shown).enumerate() {
+                let (question, answer) = card_sides(&deck.cards[card_id], false);
+                if shown > 1 {
+                    println!("{}", format!("--- Card {}/{} ---", index + 1, shown).cyan());
+                }
+                println!("❓ Question: {}", question.bold());
+                print!("Press Enter to reveal answer...");
+                let mut input = String::new();
+                if !read_line(&mut input)? {
+                    println!();
+                    break;
+                }
+                println!("✅ Answer: {}\n", answer);
+            }
+        }
+        Commands::Peek { random } => {
+            let today = today();
+            let card_id = if *random {
+                deck.get_random_cards_ids(&CardSelection::default(), None)
+                    .first()
+                    .copied()
+            } else {
+                deck.due_cards(today).first().copied()
+            };
+            match card_id {
+                None if *random => println!("No flashcards to review! Add some first."),
+                None => println!("🎉 No cards are due for review! Use --random to pick any card."),
+                Some(card_id) => {
+                    let card = &deck.cards[&card_id];
+                    let (question, answer) = card_sides(card, false);
+                    let hint_source = card
+                        .cloze_blanks()
+                        .first()
+                        .cloned()
+                        .unwrap_or(card.answer.clone());
+                    let notes = card.notes.clone();
+                    let config = Config::load().unwrap_or_else(|message| cli.fail(&message));
+                    println!("❓ Question: {}", question.bold());
+                    if let Some(mut response) = ask_self_rated(
+                        &question,
+                        &answer,
+                        &hint_source,
+                        &notes,
+                        false,
+                        &config.keys,
+                    )? {
+                        if response.hinted {
+                            apply_hint_penalty(&mut response.rating);
+                        }
+                        if rate_card(
+                            &mut deck,
+                            card_id,
+                            &response.rating,
+                            false,
+                            false,
+                            false,
+                            DEFAULT_MASTERY_THRESHOLD,
+                        ) {
+                            println!("🏅 Mastered! This card is now out of rotation.");
+                        }
+                        deck.record_study_day(today);
+                        save_deck(&mut collection, &cli, &deck)?;
+                    }
+                }
+            }
+        }
+        Commands::History { id } => match deck.get_card(*id) {
+            Some(card) if cli.json => {
+                println!("{}", serde_json::to_string_pretty(&card.metadata.history)?);
+            }
+            Some(card) if card.metadata.history.is_empty() => {
+                println!("No review history for flashcard #{} yet.", id);
+            }
+            Some(card) => {
+                println!("🕑 Review history for flashcard #{}:", id);
+                for event in &card.metadata.history {
+                    println!(
+                        "   {}  {}  quality {}",
+                        format_review_date(&event.date),
+                        if event.correct { "✅" } else { "❌" },
+                        event.quality
+                    );
+                }
+            }
+            None => println!("❌ Flashcard #{} not found.", id),
+        },
+        Commands::Delete { id, force } => match deck.get_card(*id) {
+            Some(card) => {
+                if *force
+                    || confirm(
+                        cli.styled(&format!("⚠️  Delete #{}: {}? (y/N): ", id, card.question)),
+                    )?
+                {
+                    deck.delete_card(*id);
+                    save_deck(&mut collection, &cli, &deck)?;
+                    if !cli.quiet {
+                        println!("🗑️  Deleted flashcard #{}", id);
+                    }
+                } else {
+                    println!("{}", cli.styled("❌ Delete cancelled."));
+                }
+            }
+            None => {
+                cli.report_error(&format!("Flashcard #{} not found.", id));
+            }
+        },
+        Commands::DeleteMatching {
+            tag,
+            difficulty,
+            force,
+        } => {
+            let mut matching: Vec<&Flashcard> = deck
+                .cards
+                .values()
+                .filter(|card| card.matches(tag.as_deref(), difficulty.as_ref()))
+                .collect();
+            matching.sort_by_key(|card| card.id);
+            let ids: Vec<u32> = matching.iter().map(|card| card.id).collect();
+
+            if tag.is_none() && difficulty.is_none() {
+                println!("❌ Nothing to match. Use --tag and/or --difficulty.");
+            } else if ids.is_empty() {
+                println!("No flashcards match those filters.");
+            } else if cli.dry_run {
+                println!("Would delete {} flashcards:", ids.len());
+                for card in matching {
+                    print_card_summary(&mut io::stdout(), card)?;
+                }
+            } else if *force || confirm(&format!("⚠️  Delete {} flashcards? (y/N): ", ids.len()))?
+            {
+                for id in &ids {
+                    deck.delete_card(*id);
+                }
+                save_deck(&mut collection, &cli, &deck)?;
+                println!("🗑️  Deleted {} flashcards", ids.len());
             } else {
-                println!("❌ Flashcard #{} not found.", id);
+                println!("❌ Delete cancelled.");
             }
         }
         Commands::Reset => {
             if deck.cards.is_empty() {
-                println!("❌ No flashcards to reset.");
+                cli.report_error("No flashcards to reset.");
             } else {
-                print!("⚠️  Are you sure you want to reset all statistics? This cannot be undone. (y/N): ");
-                io::stdout().flush().unwrap();
-
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-
-                if input.trim().to_lowercase() == "y" {
+                if confirm(cli.styled(
+                    "⚠️  Are you sure you want to reset all statistics? This cannot be undone. (y/N): ",
+                ))? {
                     deck.reset_all_stats();
-                    deck.save_to_file(&cli.file)?;
-                    println!("🔄 Reset all flashcard statistics.");
+                    save_deck(&mut collection, &cli, &deck)?;
+                    if !cli.quiet {
+                        println!("🔄 Reset all flashcard statistics.");
+                    }
                 } else {
-                    println!("❌ Reset cancelled.");
+                    println!("{}", cli.styled("❌ Reset cancelled."));
                 }
             }
         }
-    }
-
+        Commands::Seed { force } => {
+            if !deck.cards.is_empty() && !*force {
+                cli.report_error(&format!(
+                    "The deck already has {} flashcards. Pass --force to add the samples anyway.",
+                    deck.cards.len()
+                ));
+            } else {
+                let added = deck.add_sample_cards();
+                save_deck(&mut collection, &cli, &deck)?;
+                println!(
+                    "🌱 Added {} sample flashcards tagged '{}'. Try 'flashcard quiz'!",
+                    added, SAMPLE_TAG
+                );
+            }
+        }
+        Commands::Renumber { force } => {
+            if deck.ids_are_sequential() {
+                println!("✨ Flashcard IDs are already sequential.");
+            } else if *force
+                || confirm(cli.styled(
+                    "⚠️  Renumbering changes flashcard IDs, so IDs you noted down and a saved quiz session will no longer match. Continue? (y/N): ",
+                ))?
+            {
+                deck = deck.renumbered();
+                save_deck(&mut collection, &cli, &deck)?;
+                println!("🔢 Renumbered {} flashcards from 1.", deck.cards.len());
+            } else {
+                println!("{}", cli.styled("❌ Renumber cancelled."));
+            }
+        }
+        Commands::Edit {
+            id,
+            question,
+            answer,
+            notes,
+        } => {
+            let clean = |field, text: &Option<String>| {
+                text.as_deref().map(|text| {
+                    clean_card_text(field, text).unwrap_or_else(|message| cli.fail(&message))
+                })
+            };
+            let question = clean("question", question);
+            let answer = clean("answer", answer);
+            let is_cloze = deck
+                .get_card(*id)
+                .is_some_and(|card| card.kind == CardKind::Cloze);
+            let cloze_answer = question
+                .as_deref()
+                .filter(|_| is_cloze)
+                .map(|text| parse_cloze(text).map(|blanks| blanks.join(", ")));
+            if question.is_none() && answer.is_none() && notes.is_none() {
+                println!("❌ Nothing to edit. Use --question, --answer and/or --notes.");
+            } else if is_cloze && answer.is_some() {
+                println!("❌ Cloze cards have no separate answer. Edit the text with --question.");
+            } else if let Some(Err(message)) = &cloze_answer {
+                println!("❌ Invalid cloze text: {}", message);
+            } else if deck.update_card_text(
+                *id,
+                question.clone(),
+                cloze_answer.and_then(Result::ok).or(answer.clone()),
+            ) {
+                if let Some(notes) = notes {
+                    deck.update_card_notes(*id, notes.clone());
+                }
+                save_deck(&mut collection, &cli, &deck)?;
+                println!("✏️  Updated flashcard #{}", id);
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::Tag { id, add, remove } => {
+            let add = normalize_tags(add.clone()).unwrap_or_else(|message| cli.fail(&message));
+            let remove = normalize_tags(remove.clone()).unwrap_or_else(|message| cli.fail(&message));
+            if add.is_empty() && remove.is_empty() {
+                println!("❌ Nothing to change. Use --add and/or --remove.");
+            } else if deck.update_card_tags(*id, add, remove) {
+                save_deck(&mut collection, &cli, &deck)?;
+                let card = &deck.cards[id];
+                if card.tags.is_empty() {
+                    println!("🏷️  Flashcard #{} has no tags", id);
+                } else {
+                    println!("🏷️  Flashcard #{} tags: {}", id, card.tags.join(", "));
+                }
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::RenameTag { from, to } => {
+            let to = match normalize_tags(vec![to.clone()]) {
+                Ok(tags) if tags.len() == 1 => tags[0].clone(),
+                Ok(_) => cli.fail("The new tag name can't be empty."),
+                Err(message) => cli.fail(&message),
+            };
+            let from = normalize_tag(from);
+            let renamed = deck.rename_tag(&from, &to);
+            if renamed == 0 {
+                println!("❌ No flashcards have the tag '{}'.", from);
+            } else {
+                save_deck(&mut collection, &cli, &deck)?;
+                println!(
+                    "🏷️  Renamed tag '{}' to '{}' on {} flashcards",
+                    from, to, renamed
+                );
+            }
+        }
+        Commands::Star { id } | Commands::Unstar { id } => {
+            let starred = matches!(cli.command, Commands::Star { .. });
+            if deck.set_starred(*id, starred) {
+                save_deck(&mut collection, &cli, &deck)?;
+                if starred {
+                    println!("⭐ Starred flashcard #{}", id);
+                } else {
+                    println!("Removed the star from flashcard #{}", id);
+                }
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::Archive { id } | Commands::Unarchive { id } => {
+            let archived = matches!(cli.command, Commands::Archive { .. });
+            if deck.set_archived(*id, archived) {
+                save_deck(&mut collection, &cli, &deck)?;
+                if archived {
+                    println!("📦 Archived flashcard #{}", id);
+                } else {
+                    println!("📤 Unarchived flashcard #{}", id);
+                }
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::Archived => {
+            let mut cards: Vec<&Flashcard> =
+                deck.cards.values().filter(|card| card.archived).collect();
+            cards.sort_by_key(|card| card.id);
+            if cli.json {
+                print_cards_json(&mut io::stdout(), &cards)?;
+            } else if cards.is_empty() {
+                println!("No archived flashcards.");
+            } else {
+                println!("📦 Archived flashcards ({}):", cards.len());
+                for card in cards {
+                    print_card_summary(&mut io::stdout(), card)?;
+                }
+            }
+        }
+        Commands::Mastered => {
+            let mut cards: Vec<&Flashcard> = deck
+                .cards
+                .values()
+                .filter(|card| card.metadata.mastered)
+                .collect();
+            cards.sort_by_key(|card| card.id);
+            if cli.json {
+                print_cards_json(&mut io::stdout(), &cards)?;
+            } else if cards.is_empty() {
+                println!("No mastered flashcards yet.");
+            } else {
+                println!("🏅 Mastered flashcards ({}):", cards.len());
+                for card in cards {
+                    print_card_summary(&mut io::stdout(), card)?;
+                }
+            }
+        }
+        Commands::Unmaster { id } => {
+            if deck.unmaster(*id) {
+                save_deck(&mut collection, &cli, &deck)?;
+                println!("🔁 Flashcard #{} is back in rotation", id);
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::Export { format, fields, .. } if !fields.is_empty() && format != "csv" => {
+            println!("❌ --fields only applies to csv exports, not '{}'.", format);
+        }
+        Commands::Export { format, output, .. } if cli.dry_run => {
+            println!("🧪 Dry run: not exporting {} to {}.", format, output);
+        }
+        Commands::Export {
+            format,
+            output,
+            fields,
+        } => {
+            let count = match format.as_str() {
+                "csv" => Some((deck.export_csv(output, fields)?, "flashcards")),
+                "md" | "markdown" => Some((deck.export_markdown(output)?, "flashcards")),
+                "anki" => Some((deck.export_anki(output)?, "flashcards")),
+                "json" => Some((deck.export_json(output)?, "flashcards")),
+                "stats-csv" if deck.compute_stats(true).total_reviews == 0 => {
+                    println!("❌ No reviews to export yet. Take a quiz first!");
+                    return Ok(());
+                }
+                "stats-csv" => Some((deck.export_stats_csv(output)?, "rows")),
+                _ => None,
+            };
+            match count {
+                Some((count, what)) => println!("📤 Exported {} {} to {}", count, what, output),
+                None => println!(
+                    "❌ Unknown export format '{}'. Supported formats: csv, md, anki, json, stats-csv",
+                    format
+                ),
+            }
+        }
+        Commands::Search {
+            query,
+            questions_only,
+            answers_only,
+            case_sensitive,
+        } => {
+            let scope = if *questions_only {
+                SearchScope::Questions
+            } else if *answers_only {
+                SearchScope::Answers
+            } else {
+                SearchScope::All
+            };
+            let matches = deck.search_in(query, scope, *case_sensitive);
+
+            if cli.json {
+                print_cards_json(&mut io::stdout(), &matches)?;
+            } else if matches.is_empty() {
+                println!("No matches for '{}'.", query);
+            } else {
+                println!("🔍 Matches for '{}' ({}):", query, matches.len());
+                for card in matches {
+                    print_card_summary(&mut io::stdout(), card)?;
+                }
+            }
+        }
+        Commands::Dedup => {
+            let groups = deck.duplicate_groups();
+            if groups.is_empty() {
+                println!("✨ No duplicate flashcards found.");
+            } else {
+                for group in &groups {
+                    let duplicates: Vec<String> = group
+                        .duplicates
+                        .iter()
+                        .map(|id| format!("#{}", id))
+                        .collect();
+                    println!(
+                        "🔁 #{} {}: merging {}",
+                        group.keep,
+                        deck.cards[&group.keep].question,
+                        duplicates.join(", ")
+                    );
+                }
+
+                if cli.dry_run {
+                    let count: usize = groups.iter().map(|group| group.duplicates.len()).sum();
+                    println!("Dry run: {} duplicate flashcards would be removed.", count);
+                } else {
+                    let removed = deck.merge_duplicates(&groups);
+                    save_deck(&mut collection, &cli, &deck)?;
+                    println!("🧹 Removed {} duplicate flashcards.", removed);
+                }
+            }
+        }
+        Commands::Lint => {
+            let mut serious_issues = 0;
+            let mut issues = 0;
+            for check in &LINT_CHECKS {
+                let ids = (check.find)(&deck);
+                if ids.is_empty() {
+                    continue;
+                }
+                let marker = if check.serious { "❌" } else { "⚠️ " };
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                println!(
+                    "{} {} ({}): {}",
+                    marker,
+                    check.description,
+                    ids.len(),
+                    ids.join(", ")
+                );
+                issues += ids.len();
+                if check.serious {
+                    serious_issues += ids.len();
+                }
+            }
+
+            if issues == 0 {
+                println!("✨ No problems found in {} flashcards.", deck.cards.len());
+            } else {
+                println!(
+                    "\n{} issues found ({} serious) in {} flashcards.",
+                    issues,
+                    serious_issues,
+                    deck.cards.len()
+                );
+            }
+            if serious_issues > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Due { count_only } => {
+            let today = today();
+            let due = deck.due_cards(today);
+
+            if *count_only {
+                println!("{}", due.len());
+            } else if cli.json {
+                let cards: Vec<&Flashcard> = due.iter().map(|id| &deck.cards[id]).collect();
+                print_cards_json(&mut io::stdout(), &cards)?;
+            } else if due.is_empty() {
+                println!("🎉 No cards are due for review!");
+            } else {
+                println!("⏰ Cards due for review ({}):", due.len());
+                for id in &due {
+                    let card = &deck.cards[id];
+                    let status = if card.metadata.times_reviewed == 0 {
+                        "never reviewed".to_string()
+                    } else {
+                        match (today - card.metadata.effective_due_date()).num_days() {
+                            0 => "due today".to_string(),
+                            1 => "1 day overdue".to_string(),
+                            days => format!("{} days overdue", days),
+                        }
+                    };
+                    println!(
+                        "#{} [{}] {}",
+                        card.id,
+                        format!("{:.30}", card.question).trim(),
+                        status
+                    );
+                }
+            }
+        }
+        Commands::Goal { count } => {
+            deck.daily_goal = *count;
+            save_deck(&mut collection, &cli, &deck)?;
+            if *count == 0 {
+                println!("🎯 Daily goal turned off.");
+            } else {
+                println!(
+                    "🎯 Daily goal set to {} cards ({} reviewed today)",
+                    count,
+                    deck.reviews_on(today())
+                );
+            }
+        }
+        Commands::Schedule { id, days } => {
+            let fixed = Some(*days).filter(|days| *days > 0);
+            if deck.set_fixed_interval(*id, fixed) {
+                save_deck(&mut collection, &cli, &deck)?;
+                match fixed {
+                    Some(days) => println!(
+                        "📅 Flashcard #{} will be reviewed every {}",
+                        id,
+                        days_label(days)
+                    ),
+                    None => println!("📅 Flashcard #{} is back on its normal schedule", id),
+                }
+            } else {
+                println!("❌ Flashcard #{} not found.", id);
+            }
+        }
+        Commands::Weight { id, value } => match deck.set_weight(*id, *value) {
+            Some(weight) => {
+                save_deck(&mut collection, &cli, &deck)?;
+                println!("⚖️  Flashcard #{} now has weight {}", id, weight);
+            }
+            None => println!("❌ Flashcard #{} not found.", id),
+        },
+        Commands::Streak => {
+            let today = today();
+            println!("🔥 Current streak: {}", days_label(deck.streak_on(today)));
+            println!("🏆 Longest streak: {}", days_label(deck.longest_streak));
+            if let Some(last_study_date) = deck.last_study_date {
+                println!("   Last studied: {}", last_study_date);
+            }
+        }
+        Commands::Heatmap { weeks } => {
+            println!("📅 Study activity (last {} weeks):", weeks);
+            print_heatmap(&deck.review_counts_by_day(), today(), *weeks);
+        }
+        Commands::Boxes => {
+            println!("📦 Leitner boxes:");
+            for (index, count) in deck.box_counts().iter().enumerate() {
+                println!(
+                    "   Box {} (every {}): {} cards",
+                    index + 1,
+                    days_label(LEITNER_INTERVAL_DAYS[index] as u32),
+                    count
+                );
+            }
+        }
+        Commands::Stats {
+            include_archived,
+            verbose,
+            by_tag,
+            reviewed,
+            output,
+        } => {
+            reviewed
+                .check()
+                .unwrap_or_else(|message| cli.fail(&message));
+            let mut deck = deck.clone();
+            deck.cards.retain(|_, card| reviewed.includes(card));
+            let mut stats = deck.compute_stats(*include_archived);
+            if *verbose {
+                stats.lengths = Some(deck.compute_length_stats(*include_archived));
+            }
+            if *by_tag {
+                stats.by_tag = Some(deck.compute_tag_stats(*include_archived));
+            }
+            let mut out = OutputBuffer::new(output);
+            if cli.json {
+                writeln!(out, "{}", serde_json::to_string_pretty(&stats)?)?;
+            } else if stats.total_cards == 0
+                && (reviewed.since.is_some() || reviewed.until.is_some())
+            {
+                writeln!(out, "No flashcards were reviewed in that range.")?;
+            } else if stats.total_cards == 0 {
+                writeln!(out, "No flashcards found. Add some with 'flashcard add <question> <answer>'")?;
+            } else {
+                writeln!(out, "{}", "📈 Deck Statistics:".bold())?;
+                writeln!(out, "   Total cards: {}", stats.total_cards)?;
+                writeln!(out, "   Total reviews: {}", stats.total_reviews)?;
+                writeln!(out, "   Correct answers: {}", stats.total_correct)?;
+                writeln!(
+                    out,
+                    "   Overall success rate: {}",
+                    success_rate_text(stats.success_rate, 1)
+                )?;
+                writeln!(out, "   Never reviewed: {}", stats.never_reviewed)?;
+                writeln!(
+                    out,
+                    "   Study streak: {} (longest {})",
+                    days_label(deck.streak_on(today())),
+                    days_label(deck.longest_streak)
+                )?;
+                if let (Some(percentage), Some(date)) =
+                    (stats.best_quiz_percentage, stats.best_quiz_date)
+                {
+                    writeln!(
+                        out,
+                        "   Best quiz score: {} on {}",
+                        success_rate_text(percentage, 1),
+                        date
+                    )?;
+                }
+                writeln!(out)?;
+                writeln!(out, "📊 By difficulty:")?;
+                writeln!(out, "   🟢 Easy: {}", stats.easy_cards)?;
+                writeln!(out, "   🟡 Medium: {}", stats.medium_cards)?;
+                writeln!(out, "   🔴 Hard: {}", stats.hard_cards)?;
+
+                let describe = |id: Option<u32>| {
+                    id.map(|id| {
+                        let card = &deck.cards[&id];
+                        format!(
+                            "#{} {} ({} reviews)",
+                            card.id,
+                            format!("{:.30}", card.question).trim(),
+                            card.metadata.times_reviewed
+                        )
+                    })
+                    .unwrap_or_default()
+                };
+                writeln!(out)?;
+                writeln!(out, "🔁 Most reviewed: {}", describe(stats.most_reviewed))?;
+                writeln!(out, "💤 Least reviewed: {}", describe(stats.least_reviewed))?;
+
+                if let Some(lengths) = &stats.lengths {
+                    writeln!(out)?;
+                    writeln!(out, "📏 Lengths (average, min-max):")?;
+                    for (label, side) in [
+                        ("Questions", &lengths.questions),
+                        ("Answers", &lengths.answers),
+                    ] {
+                        writeln!(
+                            out,
+                            "   {}: {:.1} chars ({}-{}), {:.1} words ({}-{})",
+                            label,
+                            side.avg_chars,
+                            side.min_chars,
+                            side.max_chars,
+                            side.avg_words,
+                            side.min_words,
+                            side.max_words
+                        )?;
+                    }
+                    writeln!(out, "   Total words: {}", lengths.total_words)?;
+                }
+
+                if let Some(by_tag) = &stats.by_tag {
+                    writeln!(out)?;
+                    writeln!(out, "🏷️  By tag:")?;
+                    let mut tags: Vec<(&String, &TagStats)> = by_tag.iter().collect();
+                    tags.sort_by(|(a_tag, a), (b_tag, b)| {
+                        a.success_rate
+                            .total_cmp(&b.success_rate)
+                            .then_with(|| a_tag.cmp(b_tag))
+                    });
+                    for (tag, tag_stats) in &tags {
+                        writeln!(
+                            out,
+                            "   {}: {} cards, {} reviews, {} success",
+                            tag,
+                            tag_stats.cards,
+                            tag_stats.reviews,
+                            success_rate_text(tag_stats.success_rate, 1)
+                        )?;
+                    }
+                    if tags.is_empty() {
+                        writeln!(out, "   No tagged flashcards.")?;
+                    }
+                }
+            }
+            write_output(&cli, output, &out.text)?;
+        }
+        Commands::Undo => {
+            let backup = DeckCollection::backup_path(&cli.file);
+            if std::path::Path::new(&backup).exists() && cli.dry_run {
+                println!(
+                    "🧪 Dry run: {} would be restored from its backup.",
+                    cli.file
+                );
+            } else if std::path::Path::new(&backup).exists() {
+                fs::rename(&backup, &cli.file)?;
+                println!("↩️  Restored {} from its backup.", cli.file);
+            } else {
+                println!("❌ Nothing to undo.");
+            }
+        }
+        Commands::Backup { .. } if !std::path::Path::new(&cli.file).exists() => {
+            cli.report_error(&format!(
+                "{} doesn't exist yet, so there is nothing to back up.",
+                cli.file
+            ));
+        }
+        Commands::Backup { max_backups } => {
+            if cli.dry_run {
+                println!("🧪 Dry run: not backing up {}.", cli.file);
+            } else {
+                let path = create_snapshot(&backups_dir(&cli.file), &cli.file)?;
+                println!("💾 Backed up {} to {}", cli.file, path.display());
+                if let Some(keep) = max_backups {
+                    let pruned = prune_snapshots(&backups_dir(&cli.file), &cli.file, *keep)?;
+                    if pruned > 0 {
+                        println!(
+                            "🧹 Deleted {} old backups, keeping the newest {}.",
+                            pruned, keep
+                        );
+                    }
+                }
+            }
+        }
+        Commands::Restore { name: None } => {
+            let names = list_snapshots(&backups_dir(&cli.file), &cli.file)?;
+            if names.is_empty() {
+                println!("No backups yet. Make one with 'flashcard backup'.");
+            } else {
+                println!(
+                    "{}",
+                    format!("Backups in {}:", backups_dir(&cli.file).display()).bold()
+                );
+                for name in names.iter().rev() {
+                    println!("   {}", name);
+                }
+            }
+        }
+        Commands::Restore { name: Some(name) } => {
+            let path = backups_dir(&cli.file).join(name);
+            if !list_snapshots(&backups_dir(&cli.file), &cli.file)?.contains(name) {
+                cli.report_error(&format!(
+                    "No backup named '{}'. Run 'flashcard restore' to list them.",
+                    name
+                ));
+            } else if let Err(error) = DeckCollection::load_from_file(&path.to_string_lossy()) {
+                cli.report_error(&format!("Backup '{}' can't be read: {}", name, error));
+            } else if cli.dry_run {
+                println!("🧪 Dry run: {} would be restored from {}.", cli.file, name);
+            } else if confirm(&format!(
+                "⚠️  Replace {} with the backup {}? (y/N): ",
+                cli.file, name
+            ))? {
+                if std::path::Path::new(&cli.file).exists() {
+                    // Keep the current file as the undo backup, like any other save
+                    fs::copy(&cli.file, DeckCollection::backup_path(&cli.file))?;
+                }
+                fs::copy(&path, &cli.file)?;
+                println!(
+                    "↩️  Restored {} from {}. Run 'flashcard undo' to go back.",
+                    cli.file, name
+                );
+            } else {
+                println!("❌ Restore cancelled.");
+            }
+        }
+        Commands::Completions { .. } | Commands::Where => {
+            unreachable!("handled before loading the deck")
+        }
+        Commands::Decks => {
+            if collection.decks.is_empty() {
+                println!("No decks found. Add a card to create one.");
+            } else {
+                let mut names: Vec<&String> = collection.decks.keys().collect();
+                names.sort();
+
+                println!("📚 Decks ({}):", names.len());
+                for name in names {
+                    let marker = if *name == cli.deck { "*" } else { " " };
+                    println!(
+                        "{} {} ({} cards)",
+                        marker,
+                        name,
+                        collection.decks[name].cards.len()
+                    );
+                }
+            }
+        }
+        Commands::Move { id, to } | Commands::Copy { id, to, .. } => {
+            let moving = matches!(cli.command, Commands::Move { .. });
+            let keep_stats = matches!(
+                cli.command,
+                Commands::Move { .. }
+                    | Commands::Copy {
+                        keep_stats: true,
+                        ..
+                    }
+            );
+            match deck.get_card(*id).cloned() {
+                _ if *to == cli.deck => {
+                    println!("❌ Flashcard #{} is already in deck '{}'.", id, to);
+                }
+                Some(mut card) => {
+                    if !keep_stats {
+                        card.metadata.reset_stats();
+                    }
+                    let new_id = collection
+                        .decks
+                        .entry(to.clone())
+                        .or_default()
+                        .insert_with_fresh_id(card);
+                    if moving {
+                        deck.delete_card(*id);
+                    }
+                    save_deck(&mut collection, &cli, &deck)?;
+                    println!(
+                        "📦 {} flashcard #{} to deck '{}' as #{}",
+                        if moving { "Moved" } else { "Copied" },
+                        id,
+                        to,
+                        new_id
+                    );
+                }
+                None => println!("❌ Flashcard #{} not found.", id),
+            }
+        }
+        Commands::Merge {
+            other,
+            from_deck,
+            merge_stats,
+        } => {
+            let from_deck = from_deck.as_ref().unwrap_or(&cli.deck);
+            match DeckCollection::load_from_file(other) {
+                Ok(other_collection) => match other_collection.decks.get(from_deck) {
+                    Some(other_deck) => {
+                        let summary = deck.merge_deck(other_deck, *merge_stats);
+                        save_deck(&mut collection, &cli, &deck)?;
+                        println!(
+                            "🔀 Added {} flashcards from {} ({} merged, {} duplicates skipped)",
+                            summary.added, other, summary.merged, summary.skipped
+                        );
+                    }
+                    None => cli.report_error(&format!("'{}' has no deck '{}'.", other, from_deck)),
+                },
+                Err(error) => cli.report_error(&format!("Could not read '{}': {}", other, error)),
+            }
+        }
+        Commands::Import {
+            format,
+            input,
+            separator,
+            term_sep,
+            card_sep,
+        } => {
+            let bar = import_progress_bar(&cli, input);
+            let progress = |read: u64| bar.set_position(read);
+            let summary = match format.as_str() {
+                "csv" => Some(deck.import_csv(input, progress)),
+                "txt" => Some(deck.import_text(input, separator, progress)),
+                "quizlet" => Some(deck.import_quizlet(
+                    input,
+                    &unescape_separator(term_sep),
+                    &unescape_separator(card_sep),
+                    progress,
+                )),
+                _ => None,
+            };
+            // Clear the bar before anything else is printed, errors included
+            bar.finish_and_clear();
+            match summary.transpose()? {
+                Some(summary) => {
+                    save_deck(&mut collection, &cli, &deck)?;
+                    println!("📥 Imported {} flashcards from {}", summary.added, input);
+                    if summary.skipped > 0 {
+                        println!("⚠️  Skipped {} malformed entries", summary.skipped);
+                    }
+                }
+                None => println!(
+                    "❌ Unknown import format '{}'. Supported formats: csv, txt, quizlet",
+                    format
+                ),
+            }
+        }
+    }
+
     Ok(())
 }
 
-fn run_quiz(deck: &mut FlashcardDeck) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🎯 Starting quiz! Press Enter to see the answer, then rate your performance:");
-    println!("Ratings: (c)orrect + easy, (g)ot it but medium, (w)rong/hard, (q)uit\n");
+/// A progress bar over the bytes of an import file, drawn on stderr. It stays hidden under
+/// `--quiet` and when stderr isn't a terminal.
+fn import_progress_bar(cli: &Cli, input: &str) -> ProgressBar {
+    if cli.quiet || !io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let size = fs::metadata(input)
+        .map(|metadata| metadata.len())
+        .unwrap_or(0);
+    let bar = ProgressBar::new(size);
+    if let Ok(style) = ProgressStyle::with_template(
+        "📥 {bar:40} {percent:>3}% {binary_bytes}/{binary_total_bytes}",
+    ) {
+        bar.set_style(style);
+    }
+    bar
+}
+
+fn days_label(days: u32) -> String {
+    if days == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", days)
+    }
+}
 
-    let cards = deck.get_random_cards_ids();
-    let mut quiz_count = 0;
-    let mut correct_count = 0;
+/// The block used for a day with `count` reviews in the heatmap.
+fn heatmap_shade(count: u32) -> char {
+    match count {
+        0 => '·',
+        1 => '░',
+        2..=3 => '▒',
+        4..=6 => '▓',
+        _ => '█',
+    }
+}
 
-    for card_id in cards {
-        quiz_count += 1;
-        let (question, answer) = {
-            let card = &deck.cards[&card_id];
-            (card.question.clone(), card.answer.clone())
+/// The most weeks `heatmap --weeks` shows, about ten years.
+const MAX_HEATMAP_WEEKS: i64 = 520;
+
+/// Print one row per weekday and one column per week, ending with the week of `today`.
+fn print_heatmap(counts: &HashMap<chrono::NaiveDate, u32>, today: chrono::NaiveDate, weeks: u32) {
+    let mut total = 0;
+    let labels = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    for (label, days) in labels.iter().zip(heatmap_rows(counts, today, weeks)) {
+        let mut row = format!("   {} ", label);
+        for count in days {
+            match count {
+                Some(count) => {
+                    total += count;
+                    row.push(heatmap_shade(count));
+                }
+                None => row.push(' '),
+            }
+            row.push(' ');
+        }
+        println!("{}", row.trim_end());
+    }
+    println!();
+    println!(
+        "   Less {} More",
+        [0, 1, 2, 4, 7]
+            .map(|count| heatmap_shade(count).to_string())
+            .join(" ")
+    );
+    println!("   {} reviews since {}", total, heatmap_start(today, weeks));
+}
+
+/// Open a file with the platform's default application, without waiting for it to close.
+fn open_in_viewer(path: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(|_| ())
+}
+
+/// Ask a yes/no question on stdin; only an explicit "y" confirms.
+fn confirm(prompt: &str) -> io::Result<bool> {
+    print!("{}", prompt);
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_lowercase() == "y")
+}
+
+/// A success rate colored green, yellow or red depending on how good it is.
+fn success_rate_text(rate: f64, precision: usize) -> ColoredString {
+    let text = format!("{:.*}%", precision, rate);
+    if rate >= 80.0 {
+        text.green()
+    } else if rate >= 50.0 {
+        text.yellow()
+    } else {
+        text.red()
+    }
+}
+
+/// The success rate and counts of one direction of a card, like "75.0% (3/4)".
+fn direction_results_text(metadata: &CardMetadata, reversed: bool) -> String {
+    match metadata.direction_results(reversed) {
+        (_, 0) => "Not yet reviewed".to_string(),
+        (correct, total) => format!(
+            "{} ({}/{})",
+            success_rate_text(correct as f64 / total as f64 * 100.0, 1),
+            correct,
+            total
+        ),
+    }
+}
+
+/// A bordered table of cards that fits the terminal width.
+fn cards_table(cards: &[&Flashcard]) -> comfy_table::Table {
+    use comfy_table::{Cell, Color, ContentArrangement, Table, presets};
+
+    let mut table = Table::new();
+    table
+        .load_style(presets::UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(["ID", "Difficulty", "Question", "Success", "Reviews", "Last"]);
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        table.force_no_tty();
+    }
+
+    for card in cards {
+        let question = card.question.replace('\n', " ");
+        let question = if question.chars().count() > 40 {
+            format!("{}…", question.chars().take(39).collect::<String>())
+        } else {
+            question
+        };
+        let rate = card.metadata.success_rate();
+        let rate_color = if rate >= 80.0 {
+            Color::Green
+        } else if rate >= 50.0 {
+            Color::Yellow
+        } else {
+            Color::Red
         };
+        table.add_row([
+            Cell::new(format!(
+                "{}{}",
+                card.id,
+                if card.starred { " ⭐" } else { "" }
+            )),
+            Cell::new(format!("{:?}", card.metadata.difficulty)),
+            Cell::new(question),
+            Cell::new(format!("{:.0}%", rate)).fg(rate_color),
+            Cell::new(card.metadata.times_reviewed),
+            Cell::new(
+                card.metadata
+                    .last_reviewed
+                    .map_or("Never".to_string(), |date| {
+                        date.with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d")
+                            .to_string()
+                    }),
+            ),
+        ]);
+    }
+    table
+}
 
-        println!("--- Card {}/{} ---", quiz_count, deck.cards.len());
-        println!("❓ Question: {}", question);
-        print!("Press Enter to reveal answer...");
-        io::stdout().flush().unwrap();
+/// Print the compact one-card summary used by list-style commands.
+fn print_card_summary(out: &mut impl Write, card: &Flashcard) -> io::Result<()> {
+    let difficulty_emoji = match card.metadata.difficulty {
+        Difficulty::Easy => "🟢",
+        Difficulty::Medium => "🟡",
+        Difficulty::Hard => "🔴",
+    };
+
+    writeln!(
+        out,
+        "#{}{} {} [{}] Success: {}{} ({}/{})",
+        card.id,
+        if card.starred { " ⭐" } else { "" },
+        difficulty_emoji,
+        format!("{:.30}", card.question).trim(),
+        success_rate_text(card.metadata.success_rate(), 0),
+        card.metadata
+            .trend()
+            .map_or(String::new(), |trend| format!(" {}", trend.arrow())),
+        card.metadata.correct_count,
+        card.metadata.times_reviewed
+    )?;
+
+    if !card.tags.is_empty() {
+        writeln!(out, "    Tags: {}", card.tags.join(", "))?;
+    }
+    if card.metadata.times_reviewed > 0 {
+        writeln!(
+            out,
+            "    Last reviewed: {}",
+            card.metadata
+                .last_reviewed
+                .as_ref()
+                .map_or("Never".to_string(), format_last_reviewed)
+        )?;
+    }
+    writeln!(out)
+}
+
+fn print_cards_json(
+    out: &mut impl Write,
+    cards: &[&Flashcard],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let reports: Vec<CardReport> = cards.iter().map(|card| CardReport::new(card)).collect();
+    writeln!(out, "{}", serde_json::to_string_pretty(&reports)?)?;
+    Ok(())
+}
+
+/// A command's output, collected to go to stdout or a file. Colors are off while it's
+/// headed for a file, and go back to how they were once the buffer is dropped.
+struct OutputBuffer {
+    text: Vec<u8>,
+    restore_colors: Option<bool>,
+}
+
+impl OutputBuffer {
+    fn new(output: &Option<String>) -> Self {
+        let restore_colors = output.as_ref().map(|_| {
+            let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+            colored::control::set_override(false);
+            colorize
+        });
+        OutputBuffer {
+            text: Vec::new(),
+            restore_colors,
+        }
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.text.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for OutputBuffer {
+    fn drop(&mut self) {
+        if let Some(colorize) = self.restore_colors {
+            colored::control::set_override(colorize);
+        }
+    }
+}
+
+/// Print collected output, or write it to `output` (creating its directory) and say where.
+fn write_output(
+    cli: &Cli,
+    output: &Option<String>,
+    text: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match output {
+        None => io::stdout().write_all(text)?,
+        Some(path) if cli.dry_run => println!("🧪 Dry run: not writing to {}.", path),
+        Some(path) => {
+            if let Some(dir) = std::path::Path::new(path).parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, text)?;
+            println!(
+                "{}",
+                cli.styled(&format!("💾 Wrote the output to {}", path))
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Load the deck file, explaining parse errors and offering a way out instead of aborting.
+fn load_or_recover(cli: &Cli) -> Result<DeckCollection, Box<dyn std::error::Error>> {
+    let error = match DeckCollection::load_from_file(&cli.file) {
+        Ok(collection) => return Ok(collection),
+        Err(error) => error,
+    };
+    let (parse_error, is_eof) = if let Some(error) = error.downcast_ref::<serde_json::Error>() {
+        (error.to_string(), error.is_eof())
+    } else if let Some(error) = error.downcast_ref::<json5::Error>() {
+        (error.to_string(), false)
+    } else {
+        return Err(error);
+    };
+
+    // Undo and restore only need the backups, so let them through to repair the file
+    if matches!(cli.command, Commands::Undo | Commands::Restore { .. }) {
+        return Ok(DeckCollection::new());
+    }
+
+    eprintln!(
+        "❌ Could not read deck file '{}': {}",
+        cli.file, parse_error
+    );
+    if is_eof {
+        eprintln!("   The file ends unexpectedly, probably because a save was interrupted.");
+    }
+
+    let backup = DeckCollection::backup_path(&cli.file);
+    if std::path::Path::new(&backup).exists() {
+        eprintln!(
+            "💾 A backup exists at '{}'. Run 'flashcard undo' to restore it.",
+            backup
+        );
+    }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    let corrupt = format!("{}.corrupt", cli.file);
+    if confirm(&format!(
+        "⚠️  Start fresh with an empty file? The broken file will be moved to '{}'. (y/N): ",
+        corrupt
+    ))? {
+        if !cli.dry_run {
+            fs::rename(&cli.file, &corrupt)?;
+        }
+        Ok(DeckCollection::new())
+    } else {
+        std::process::exit(1);
+    }
+}
 
+/// Where `backup` puts snapshots: `backups` inside $FLASHCARDS_DIR or the config
+/// directory, or next to the deck file when there is neither.
+fn backups_dir(deck_file: &str) -> std::path::PathBuf {
+    flashcards_dir()
+        .or_else(|| {
+            std::path::Path::new(deck_file)
+                .parent()
+                .map(|dir| dir.to_path_buf())
+        })
+        .unwrap_or_default()
+        .join("backups")
+}
+
+fn save_deck(
+    collection: &mut DeckCollection,
+    cli: &Cli,
+    deck: &FlashcardDeck,
+) -> Result<(), Box<dyn std::error::Error>> {
+    collection.decks.insert(cli.deck.clone(), deck.clone());
+    if cli.dry_run {
+        println!("{}", cli.styled("🧪 (dry-run: not saved)"));
+        return Ok(());
+    }
+    collection.save_to_file(&cli.file)?;
+    // Older files are migrated in memory on load and only take the new format here
+    if let Some(version) = collection.migrated_from.take() {
+        eprintln!(
+            "{}",
+            cli.styled(&format!(
+                "🔄 Upgraded '{}' from schema version {} to {}. The old file is kept as '{}'.",
+                cli.file,
+                version,
+                SCHEMA_VERSION,
+                DeckCollection::backup_path(&cli.file)
+            ))
+        );
+    }
+    Ok(())
+}
+
+/// Which side of each card is shown as the prompt.
+#[derive(PartialEq)]
+enum QuizDirection {
+    Forward,
+    Reverse,
+    Both,
+}
+
+impl QuizDirection {
+    fn label(&self) -> &'static str {
+        match self {
+            QuizDirection::Forward => "question → answer",
+            QuizDirection::Reverse => "answer → question",
+            QuizDirection::Both => "both directions",
+        }
+    }
+}
+
+fn print_notes(notes: &Option<String>) {
+    if let Some(notes) = notes {
+        println!("📝 Notes: {}", notes);
+    }
+}
+
+fn choice_label(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// A rating together with how long the user took to give it.
+struct Response {
+    rating: Rating,
+    elapsed: Duration,
+    hinted: bool,
+}
+
+impl Response {
+    fn new(rating: Rating, started: Instant, hinted: bool) -> Self {
+        Response {
+            rating,
+            elapsed: started.elapsed(),
+            hinted,
+        }
+    }
+}
+
+/// A card answered with the help of a hint can't count as easy.
+fn apply_hint_penalty(rating: &mut Rating) {
+    if rating.difficulty == Difficulty::Easy {
+        println!("💡 You used a hint, so this counts as medium difficulty.\n");
+        *rating = Rating::new(Difficulty::Medium, 4);
+    }
+}
+
+/// Prompt for text that may span several lines, ending at an empty line or end of input.
+fn read_multiline(label: &str) -> io::Result<String> {
+    println!("{} (finish with an empty line):", label);
+    let mut lines = Vec::new();
+    let mut input = String::new();
+    while read_line(&mut input)? && !input.trim_end_matches(['\r', '\n']).is_empty() {
+        lines.push(input.trim_end_matches(['\r', '\n']).to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Read one line of input, returning `false` once stdin is closed.
+fn read_line(input: &mut String) -> io::Result<bool> {
+    io::stdout().flush().unwrap();
+    input.clear();
+    Ok(io::stdin().read_line(input)? > 0)
+}
+
+/// Reveal the answer on Enter and let the user rate themselves. `None` means quit.
+fn ask_self_rated(
+    question: &str,
+    answer: &str,
+    hint_source: &str,
+    notes: &Option<String>,
+    scale5: bool,
+    keys: &KeyBindings,
+) -> io::Result<Option<Response>> {
+    let mut input = String::new();
+    let mut hinted = false;
+    loop {
+        if hinted {
+            print!("Press Enter to reveal answer...");
+        } else {
+            print!("Press Enter to reveal answer (h for a hint)...");
+        }
+        if !read_line(&mut input)? {
+            return Ok(None);
+        }
+        if hinted || input.trim().to_lowercase() != "h" {
+            break;
+        }
+        println!("💡 Hint: {}", hint_for(hint_source));
+        hinted = true;
+    }
+
+    println!("✅ Answer: {}", answer);
+    print_notes(notes);
+    println!();
+    let show_again = || {
+        println!("❓ Question: {}", question.bold());
         println!("✅ Answer: {}", answer);
+        print_notes(notes);
         println!();
+    };
 
-        loop {
-            print!("Rate your performance (c/g/w/q): ");
-            io::stdout().flush().unwrap();
+    let started = Instant::now();
+    if scale5 {
+        return Ok(ask_quality_score(keys, show_again)?
+            .map(|rating| Response::new(rating, started, hinted)));
+    }
+    loop {
+        print!(
+            "Rate your performance ({}/{}/{}/{}, {} to show the card again): ",
+            keys.easy, keys.medium, keys.hard, keys.quit, keys.repeat
+        );
+        if !read_line(&mut input)? {
+            return Ok(None);
+        }
 
-            input.clear();
-            io::stdin().read_line(&mut input)?;
+        let is = |key: char| KeyBindings::pressed(key, &input);
+        let rating = if is(keys.easy) {
+            println!("{}\n", "✨ Marked as correct & easy!".green());
+            Rating::new(Difficulty::Easy, 5)
+        } else if is(keys.medium) {
+            println!(
+                "{}\n",
+                "👍 Marked as correct but medium difficulty!".yellow()
+            );
+            Rating::new(Difficulty::Medium, 4)
+        } else if is(keys.hard) {
+            println!("{}\n", "📚 Marked as hard - review this one more!".red());
+            Rating::new(Difficulty::Hard, 1)
+        } else if is(keys.quit) {
+            return Ok(None);
+        } else if is(keys.repeat) {
+            show_again();
+            continue;
+        } else {
+            println!(
+                "Invalid input! Use: {} (correct/easy), {} (got it/medium), {} (wrong/hard), {} (quit), {} (show again)",
+                keys.easy, keys.medium, keys.hard, keys.quit, keys.repeat
+            );
+            continue;
+        };
+        return Ok(Some(Response::new(rating, started, hinted)));
+    }
+}
 
-            match input.trim().to_lowercase().as_str() {
-                "c" => {
-                    deck.update_card_difficulty(card_id, Difficulty::Easy, true);
-                    correct_count += 1;
-                    println!("✨ Marked as correct & easy!\n");
-                    break;
-                }
-                "g" => {
-                    deck.update_card_difficulty(card_id, Difficulty::Medium, true);
-                    correct_count += 1;
-                    println!("👍 Marked as correct but medium difficulty!\n");
-                    break;
-                }
-                "w" => {
-                    deck.update_card_difficulty(card_id, Difficulty::Hard, false);
-                    println!("📚 Marked as hard - review this one more!\n");
-                    break;
-                }
-                "q" => {
-                    println!("Quiz ended early!");
-                    print_quiz_summary(quiz_count - 1, correct_count);
-                    return Ok(());
+/// Ask for a 0-5 quality score until a valid one is entered, or the quit key.
+fn ask_quality_score(keys: &KeyBindings, show_again: impl Fn()) -> io::Result<Option<Rating>> {
+    let mut input = String::new();
+    loop {
+        print!(
+            "Rate your recall from 0 (blackout) to 5 (perfect), {} to quit, {} to show the card again: ",
+            keys.quit, keys.repeat
+        );
+        if !read_line(&mut input)? {
+            return Ok(None);
+        }
+
+        if KeyBindings::pressed(keys.quit, &input) {
+            return Ok(None);
+        }
+        if KeyBindings::pressed(keys.repeat, &input) {
+            show_again();
+            continue;
+        }
+        let input = input.trim();
+        match input.parse::<u8>() {
+            Ok(quality) if input.len() == 1 && quality <= 5 => {
+                let rating = Rating::from_quality(quality);
+                let message = format!("Rated {}/5", quality);
+                if rating.is_correct() {
+                    println!("{}\n", message.green());
+                } else {
+                    println!("{}\n", message.red());
                 }
-                _ => {
-                    println!("Invalid input! Use: c (correct/easy), g (got it/medium), w (wrong/hard), q (quit)");
-                    continue;
+                return Ok(Some(rating));
+            }
+            _ => println!(
+                "Invalid input! Enter a single digit from 0 to 5, or {} to quit",
+                keys.quit
+            ),
+        }
+    }
+}
+
+/// Grade a typed answer against the expected one. `None` means quit.
+fn ask_typed(
+    answer: &str,
+    blanks: &[String],
+    notes: &Option<String>,
+    options: &QuizArgs,
+) -> io::Result<Option<Response>> {
+    let expected = if blanks.is_empty() {
+        vec![answer.to_string()]
+    } else {
+        blanks.to_vec()
+    };
+
+    let mut input = String::new();
+    let mut given = Vec::new();
+    let mut hinted = false;
+    let started = Instant::now();
+    while given.len() < expected.len() {
+        if expected.len() > 1 {
+            print!("✍️  Blank {}/{}: ", given.len() + 1, expected.len());
+        } else {
+            print!("✍️  Your answer: ");
+        }
+        if !read_line(&mut input)? || input.trim() == ":q" {
+            return Ok(None);
+        }
+        if input.trim() == ":h" {
+            println!("💡 Hint: {}", hint_for(&expected[given.len()]));
+            hinted = true;
+            continue;
+        }
+        given.push(input.clone());
+    }
+    let elapsed = started.elapsed();
+
+    let all_match = |check: &dyn Fn(&str, &str) -> bool| {
+        expected
+            .iter()
+            .zip(&given)
+            .all(|(expected, given)| check(expected, given))
+    };
+    let ignore_accents = options.ignore_accents;
+    let rating = if all_match(&|expected, given| check_answer(expected, given, ignore_accents)) {
+        println!("{}", "✨ Correct!".green());
+        Rating::new(Difficulty::Easy, 5)
+    } else if options.fuzzy.is_some_and(|max_distance| {
+        all_match(&|expected, given| {
+            check_answer_fuzzy(expected, given, max_distance, ignore_accents)
+        })
+    }) {
+        println!(
+            "{} The answer was: {}",
+            "👌 Close! Accepted.".yellow(),
+            answer
+        );
+        Rating::new(Difficulty::Medium, 4)
+    } else {
+        println!("{} The answer was: {}", "📚 Not quite!".red(), answer);
+        Rating::new(Difficulty::Hard, 1)
+    };
+    print_notes(notes);
+    println!();
+    Ok(Some(Response {
+        rating,
+        elapsed,
+        hinted,
+    }))
+}
+
+/// Show the answer among shuffled distractors and grade the picked letter. `None` means quit.
+fn ask_multiple_choice(
+    answer: &str,
+    notes: &Option<String>,
+    mut choices: Vec<String>,
+) -> io::Result<Option<Response>> {
+    choices.push(answer.to_string());
+    choices.shuffle(&mut rand::rng());
+
+    for (index, choice) in choices.iter().enumerate() {
+        println!("   {}) {}", choice_label(index), choice);
+    }
+
+    let mut input = String::new();
+    let started = Instant::now();
+    let picked = loop {
+        print!("Your choice: ");
+        let open = read_line(&mut input)?;
+
+        let letter = input.trim().to_uppercase();
+        if !open || letter == "Q" {
+            return Ok(None);
+        }
+        match (0..choices.len()).find(|index| choice_label(*index).to_string() == letter) {
+            Some(index) => break &choices[index],
+            None => println!(
+                "Invalid input! Pick a letter from A to {}, or q to quit",
+                choice_label(choices.len() - 1)
+            ),
+        }
+    };
+    let elapsed = started.elapsed();
+
+    let rating = if picked == answer {
+        println!("{}", "✨ Correct!".green());
+        Rating::new(Difficulty::Medium, 4)
+    } else {
+        println!("{} The answer was: {}", "📚 Not quite!".red(), answer);
+        Rating::new(Difficulty::Hard, 1)
+    };
+    print_notes(notes);
+    println!();
+    Ok(Some(Response {
+        rating,
+        elapsed,
+        hinted: false,
+    }))
+}
+
+/// Run a quiz session, saving where it stopped if the user quits and clearing it once done.
+fn run_quiz_session(
+    deck: &mut FlashcardDeck,
+    session: QuizSession,
+    options: &QuizArgs,
+    cli: &Cli,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = QuizSession::path(&cli.file);
+    let config = Config::load().unwrap_or_else(|message| cli.fail(&message));
+    let sound = options.sound && !cli.quiet;
+    match run_quiz(deck, session, options, &config.keys, sound)? {
+        _ if cli.dry_run => {}
+        Some(unfinished) => {
+            unfinished.save(&path)?;
+            println!("💾 Progress saved. Run 'flashcard quiz --resume' to continue.");
+        }
+        None if path.exists() => fs::remove_file(&path)?,
+        None => {}
+    }
+    Ok(())
+}
+
+/// How many cards come between a wrong answer and its retry in `quiz --retry`.
+const RETRY_GAP: usize = 3;
+/// How many times `quiz --retry` brings back the same card.
+const MAX_RETRIES: u32 = 3;
+
+/// Quiz the session's remaining cards. Returns the session again if the user quit early.
+/// Only the first answer to a card is recorded; `--retry` repeats are practice.
+fn run_quiz(
+    deck: &mut FlashcardDeck,
+    mut session: QuizSession,
+    options: &QuizArgs,
+    keys: &KeyBindings,
+    sound: bool,
+) -> Result<Option<QuizSession>, Box<dyn std::error::Error>> {
+    if options.choices.is_some() {
+        println!("🎯 Starting quiz! Pick the letter of the right answer (q to quit)\n");
+    } else if options.typed {
+        println!(
+            "🎯 Starting quiz! Type your answer and press Enter (type :h for a hint, :q to quit)\n"
+        );
+    } else if options.scale5 {
+        println!("🎯 Starting quiz! Press Enter to see the answer, then score your recall:");
+        println!("5 perfect, 4 hesitant, 3 difficult, 2-0 wrong (3 or more counts as correct)\n");
+    } else {
+        println!("🎯 Starting quiz! Press Enter to see the answer, then rate your performance:");
+        println!(
+            "Ratings: {} correct + easy, {} got it but medium, {} wrong/hard, {} quit\n",
+            keys.easy, keys.medium, keys.hard, keys.quit
+        );
+    }
+
+    let session_len = session.session_len;
+    let cards = std::mem::take(&mut session.remaining);
+    let already_asked = session_len.saturating_sub(cards.len());
+    let mut answered = session.answered;
+    let mut correct_count = session.correct_count;
+    let mut answered_now = 0;
+    let mut ended_early = false;
+    let mut total_response_time = Duration::ZERO;
+    let mut hints_used = 0;
+    let mut first_tries = 0;
+    let mut retries = 0;
+    let mut retries_correct = 0;
+    let mut rng = rand::rng();
+    let direction = options.direction();
+    let budget = options.time_budget();
+    let mut time_up = false;
+    let started = Instant::now();
+
+    // Each entry is a card and how many times it has been retried
+    let mut queue: VecDeque<(u32, u32)> = cards.into_iter().map(|id| (id, 0)).collect();
+    while let Some((card_id, retry)) = queue.pop_front() {
+        let time_left = budget.map(|budget| budget.saturating_sub(started.elapsed()));
+        if time_left.is_some_and(|left| left.is_zero()) {
+            session.remaining = unanswered_cards(card_id, retry, queue);
+            time_up = true;
+            ended_early = true;
+            break;
+        }
+        let card = &deck.cards[&card_id];
+        let reversed = match direction {
+            QuizDirection::Forward => false,
+            QuizDirection::Reverse => true,
+            // Ask a direction that is due when only one of them is
+            QuizDirection::Both if !options.all && !options.leitner => {
+                let today = today();
+                match (
+                    card.metadata.is_due(today),
+                    card.metadata.is_due_in(today, true),
+                ) {
+                    (true, false) => false,
+                    (false, true) => true,
+                    _ => rng.random_bool(0.5),
                 }
             }
+            QuizDirection::Both => rng.random_bool(0.5),
+        };
+        let notes = card.notes.clone().filter(|_| options.show_notes);
+        let blanks = card.cloze_blanks();
+        let reversed = reversed && card.kind == CardKind::Basic;
+        let (question, answer) = card_sides(card, reversed);
+        let choice_answer = if reversed {
+            card.question.clone()
+        } else {
+            card.answer.clone()
+        };
+        let hint_source = blanks.first().cloned().unwrap_or(choice_answer.clone());
+
+        let header = if retry == 0 {
+            first_tries += 1;
+            format!(
+                "--- Card {}/{} ---",
+                already_asked + first_tries,
+                session_len
+            )
+        } else {
+            format!("--- Retry {}/{} ---", retry, MAX_RETRIES)
+        };
+        match time_left {
+            Some(left) => println!("{} ⏳ {} left", header.cyan(), format_countdown(left)),
+            None => println!("{}", header.cyan()),
+        }
+        println!("❓ Question: {}", question.bold());
+
+        let response = if let Some(choice_count) = options.choices {
+            let distractors =
+                pick_distractors(deck, card_id, reversed, usize::from(choice_count) - 1);
+            ask_multiple_choice(&choice_answer, &notes, distractors)?
+        } else if options.typed {
+            ask_typed(&answer, &blanks, &notes, options)?
+        } else {
+            ask_self_rated(
+                &question,
+                &answer,
+                &hint_source,
+                &notes,
+                options.scale5,
+                keys,
+            )?
+        };
+
+        let Some(Response {
+            mut rating,
+            elapsed,
+            hinted,
+        }) = response
+        else {
+            session.remaining = unanswered_cards(card_id, retry, queue);
+            ended_early = true;
+            break;
+        };
+        if hinted {
+            hints_used += 1;
+            apply_hint_penalty(&mut rating);
+        }
+        if options.timed {
+            if exceeds_time_limit(elapsed, options.time_limit()) {
+                println!(
+                    "⏰ Too slow! {:.1}s is over the time limit, marked as wrong.\n",
+                    elapsed.as_secs_f64()
+                );
+                rating = Rating::new(Difficulty::Hard, 1);
+            } else {
+                println!("⏱️  Answered in {:.1}s\n", elapsed.as_secs_f64());
+            }
+            if retry == 0 {
+                deck.record_response_time(card_id, elapsed);
+                total_response_time += elapsed;
+            }
+        }
+        if sound {
+            ring_bell(rating.is_correct())?;
+        }
+        if retry > 0 {
+            if rating.is_correct() {
+                retries_correct += 1;
+            }
+        } else {
+            if rate_card(
+                deck,
+                card_id,
+                &rating,
+                reversed,
+                options.leitner,
+                options.gradual,
+                options.mastery_threshold,
+            ) {
+                println!("🏅 Mastered! This card is now out of rotation.\n");
+            }
+            answered += 1;
+            answered_now += 1;
+            if rating.is_correct() {
+                correct_count += 1;
+            }
+        }
+        if options.retry && !rating.is_correct() && retry < MAX_RETRIES {
+            queue.insert(RETRY_GAP.min(queue.len()), (card_id, retry + 1));
+            retries += 1;
+            println!("🔁 This card will come back in a moment.\n");
         }
     }
 
-    print_quiz_summary(quiz_count, correct_count);
+    if time_up {
+        println!("⏰ Time's up!");
+    } else if ended_early {
+        println!("Quiz ended early!");
+    }
+    print_quiz_summary(answered, correct_count, session_len, &direction);
+    if !ended_early && deck.record_quiz_score(correct_count, answered, today()) {
+        println!("🏆 New best quiz score for this deck! Keep it up.");
+    }
+    if retries > 0 {
+        println!(
+            "   Results count first tries only. Retries: {} ({} right)",
+            retries, retries_correct
+        );
+    }
+    if options.timed && answered_now > 0 {
+        println!(
+            "   Average response time: {:.1}s",
+            total_response_time.as_secs_f64() / answered_now as f64
+        );
+    }
+
+    if hints_used > 0 {
+        println!("   Hints used: {}", hints_used);
+    }
+
+    if answered_now > 0 {
+        deck.record_study_day(today());
+        println!("🔥 Study streak: {}", days_label(deck.current_streak));
+        if deck.daily_goal > 0 {
+            let reviewed = deck.reviews_on(today());
+            println!(
+                "🎯 Daily goal: {}/{} cards reviewed today",
+                reviewed, deck.daily_goal
+            );
+            if reviewed >= deck.daily_goal {
+                println!("🏆 Goal reached! Great work, see you tomorrow.");
+            }
+        }
+    }
+
+    if ended_early {
+        session.answered = answered;
+        session.correct_count = correct_count;
+        Ok(Some(session))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Ring the terminal bell once for a right answer and twice for a wrong one.
+fn ring_bell(correct: bool) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x07")?;
+    stdout.flush()?;
+    if !correct {
+        // A short pause so the terminal plays two separate bells
+        std::thread::sleep(Duration::from_millis(150));
+        stdout.write_all(b"\x07")?;
+        stdout.flush()?;
+    }
     Ok(())
 }
 
-fn print_quiz_summary(total: usize, correct: usize) {
+/// The cards still to be asked for the first time, starting with the one in progress.
+/// Retries are dropped; only cards not yet answered are saved for --resume.
+fn unanswered_cards(card_id: u32, retry: u32, queue: VecDeque<(u32, u32)>) -> Vec<u32> {
+    std::iter::once((card_id, retry))
+        .chain(queue)
+        .filter(|&(_, retry)| retry == 0)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Time left in a quiz as `m:ss`, rounded up so the last second still shows as 0:01.
+fn format_countdown(left: Duration) -> String {
+    let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn print_quiz_summary(total: usize, correct: usize, session_len: usize, direction: &QuizDirection) {
     println!("🎉 Quiz Complete!");
     println!(
         "📊 Results: {}/{} correct ({:.1}%)",
@@ -388,4 +3009,69 @@ fn print_quiz_summary(total: usize, correct: usize) {
             0.0
         }
     );
+    if *direction != QuizDirection::Forward {
+        println!("   Direction: {}", direction.label());
+    }
+    if total < session_len {
+        println!(
+            "   Answered {} of {} cards in this session",
+            total, session_len
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiz_args(flags: &[&str]) -> QuizArgs {
+        let args = ["flashcard", "quiz"].iter().chain(flags);
+        match Cli::parse_from(args).command {
+            Commands::Quiz(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn session_cards_keeps_at_most_the_limit() {
+        let mut deck = FlashcardDeck::new();
+        for number in 1..=10 {
+            deck.add_card(format!("Question {}", number), format!("Answer {}", number));
+        }
+        let cards = deck.get_random_cards_ids(&CardSelection::default(), Some(1));
+        let session_cards =
+            |flags: &[&str]| quiz_args(flags).session_cards(&deck, cards.clone(), today());
+
+        let limited = session_cards(&["--limit", "3"]);
+        assert_eq!(limited, cards[..3]);
+        assert_eq!(QuizSession::new(DEFAULT_DECK, limited).session_len, 3);
+        assert_eq!(session_cards(&["--limit", "20"]), cards);
+        assert_eq!(session_cards(&[]), cards);
+    }
+
+    #[test]
+    fn reviewed_range_rejects_since_after_until() {
+        let range = |since: &str, until: &str| ReviewedRange {
+            since: since.parse().ok(),
+            until: until.parse().ok(),
+        };
+        assert!(range("2024-03-01", "2024-03-31").check().is_ok());
+        assert!(range("2024-03-01", "").check().is_ok());
+        assert_eq!(
+            range("2024-04-01", "2024-03-31").check(),
+            Err("--since 2024-04-01 is after --until 2024-03-31.".to_string())
+        );
+    }
+
+    #[test]
+    fn output_buffer_for_a_file_restores_colors_when_dropped() {
+        colored::control::set_override(true);
+        {
+            let mut out = OutputBuffer::new(&Some("stats.txt".to_string()));
+            writeln!(out, "{}", "bold".bold()).unwrap();
+            assert_eq!(out.text, b"bold\n");
+        }
+        assert!(colored::control::SHOULD_COLORIZE.should_colorize());
+        colored::control::unset_override();
+    }
 }